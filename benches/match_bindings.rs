@@ -0,0 +1,58 @@
+use burn_contracts::shapes::{CompiledPattern, ShapePattern};
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+
+const PATTERN: &str = "b ... (h p) (w p) c";
+const SHAPE: [usize; 6] = [2, 9, 9, 20 * 4, 10 * 4, 3];
+const BINDINGS: [(&str, usize); 2] = [("p", 4), ("c", 3)];
+
+const SCOPED_PATTERN: &str = "b t (enc.attn.h enc.attn.d)";
+const SCOPED_SHAPE: [usize; 3] = [2, 16, 8 * 64];
+const SCOPED_BINDINGS: [(&str, usize); 2] = [("enc.h", 8), ("d", 64)];
+
+fn bench_match(c: &mut Criterion) {
+    let mut group = c.benchmark_group("match");
+
+    group.bench_function("cached_parse+match_bindings", |bench| {
+        bench.iter(|| {
+            ShapePattern::cached_parse(black_box(PATTERN))
+                .unwrap()
+                .match_bindings(black_box(&SHAPE), &BINDINGS)
+                .unwrap()
+                .select(["b", "h", "w"])
+        });
+    });
+
+    let pattern = ShapePattern::parse(PATTERN).unwrap();
+    group.bench_function("match_bindings", |bench| {
+        bench.iter(|| {
+            pattern
+                .match_bindings(black_box(&SHAPE), &BINDINGS)
+                .unwrap()
+                .select(["b", "h", "w"])
+        });
+    });
+
+    let compiled = CompiledPattern::parse(PATTERN).unwrap();
+    let [b, h, w] = compiled.slots(["b", "h", "w"]).unwrap();
+    group.bench_function("compiled.match_slots", |bench| {
+        bench.iter(|| {
+            let values: [usize; 5] = compiled.match_slots(black_box(&SHAPE), &BINDINGS).unwrap();
+            [values[b], values[h], values[w]]
+        });
+    });
+
+    let scoped = CompiledPattern::parse(SCOPED_PATTERN).unwrap();
+    group.bench_function("compiled.match_slots/scoped", |bench| {
+        bench.iter(|| {
+            let values: [usize; 4] = scoped
+                .match_slots(black_box(&SCOPED_SHAPE), &SCOPED_BINDINGS)
+                .unwrap();
+            values
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_match);
+criterion_main!(benches);
@@ -0,0 +1,479 @@
+//! The shape pattern grammar.
+//!
+//! Shared by the `burn-contracts` runtime parser and the `burn-contracts-macros`
+//! compile-time `pattern!` macro; so both accept exactly the same patterns.
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while};
+use nom::character::complete::{digit1, multispace0, multispace1, satisfy};
+use nom::combinator::{cut, map, map_res, not, recognize};
+use nom::error::{ContextError, ErrorKind, FromExternalError, ParseError, context};
+use nom::multi::{many0, many1, separated_list1};
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::{IResult, Parser};
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// A syntactic pattern component.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Component {
+    Dim(String),
+    Ellipsis,
+    Composite(Vec<String>),
+    /// A fixed size: `3`.
+    Literal(usize),
+    /// A reference to a named sub-pattern: `$name`.
+    Alias(String),
+}
+
+/// A syntax error in a pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SyntaxError {
+    /// The byte range of the offending input; empty at the end of the pattern.
+    pub span: Range<usize>,
+
+    /// The tokens which would have been accepted at `span.start`.
+    pub expected: Vec<&'static str>,
+}
+
+impl SyntaxError {
+    /// Describe what was expected, e.g. "expected ')'".
+    #[must_use]
+    pub fn message(&self) -> String {
+        describe_expected(&self.expected)
+    }
+
+    /// Render the pattern, with a caret marker under the error span.
+    #[must_use]
+    pub fn render(
+        &self,
+        input: &str,
+    ) -> String {
+        render_caret(input, &self.span)
+    }
+}
+
+/// Describe a list of expected tokens, e.g. "expected ')'".
+#[must_use]
+pub fn describe_expected<S: AsRef<str>>(expected: &[S]) -> String {
+    match expected {
+        [] => "unexpected input".to_string(),
+        [token] => format!("expected {}", token.as_ref()),
+        tokens => {
+            let tokens: Vec<&str> = tokens.iter().map(AsRef::as_ref).collect();
+            format!("expected one of {}", tokens.join(", "))
+        }
+    }
+}
+
+/// Render `input`, with a caret marker under `span`.
+///
+/// The marker is at least one character wide; so an empty span at the end is visible.
+#[must_use]
+pub fn render_caret(
+    input: &str,
+    span: &Range<usize>,
+) -> String {
+    let start = span.start.min(input.len());
+    let end = span.end.clamp(start, input.len());
+    let column = input[..start].chars().count();
+    let width = input[start..end].chars().count().max(1);
+    format!("{input}\n{}{}", " ".repeat(column), "^".repeat(width))
+}
+
+/// A nom error which tracks the expected tokens at the furthest failure.
+#[derive(Debug, Clone, PartialEq)]
+struct GrammarError<'a> {
+    input: &'a str,
+    expected: Vec<&'static str>,
+}
+
+impl<'a> ParseError<&'a str> for GrammarError<'a> {
+    fn from_error_kind(
+        input: &'a str,
+        _kind: ErrorKind,
+    ) -> Self {
+        Self {
+            input,
+            expected: Vec::new(),
+        }
+    }
+
+    fn append(
+        _input: &'a str,
+        _kind: ErrorKind,
+        other: Self,
+    ) -> Self {
+        other
+    }
+
+    /// Keep the furthest error; merging alternatives at the same position.
+    fn or(
+        self,
+        other: Self,
+    ) -> Self {
+        match self.input.len().cmp(&other.input.len()) {
+            Ordering::Less => self,
+            Ordering::Greater => other,
+            Ordering::Equal => {
+                let mut expected = self.expected;
+                for token in other.expected {
+                    if !expected.contains(&token) {
+                        expected.push(token);
+                    }
+                }
+                Self {
+                    input: self.input,
+                    expected,
+                }
+            }
+        }
+    }
+}
+
+impl<'a> ContextError<&'a str> for GrammarError<'a> {
+    /// Label a failure at the start of a context with the context name;
+    /// deeper failures keep their own, more specific, expectations.
+    fn add_context(
+        input: &'a str,
+        ctx: &'static str,
+        other: Self,
+    ) -> Self {
+        if other.input.len() == input.len() {
+            Self {
+                input,
+                expected: vec![ctx],
+            }
+        } else {
+            other
+        }
+    }
+}
+
+/// An out-of-range size; reported like any other unexpected input.
+impl<'a, E> FromExternalError<&'a str, E> for GrammarError<'a> {
+    fn from_external_error(
+        input: &'a str,
+        kind: ErrorKind,
+        _e: E,
+    ) -> Self {
+        Self::from_error_kind(input, kind)
+    }
+}
+
+type GrammarResult<'a, O> = IResult<&'a str, O, GrammarError<'a>>;
+
+/// Parse a pattern into its components.
+///
+/// Leading and trailing whitespace is ignored.
+///
+/// ## Errors
+///
+/// Returns a `SyntaxError` at the furthest position which cannot be parsed.
+pub fn parse_components(input: &str) -> Result<Vec<Component>, SyntaxError> {
+    let body = input.trim_start();
+    let lead = input.len() - body.len();
+    let body = body.trim_end();
+
+    let err = match components_parser(body) {
+        Ok(("", components)) => return Ok(components),
+        // `many1` stops quietly at the first bad component; re-parse it for the reason.
+        Ok((remaining, _)) => match component_parser(remaining) {
+            Err(nom::Err::Error(err) | nom::Err::Failure(err)) => err,
+            _ => GrammarError {
+                input: remaining,
+                expected: Vec::new(),
+            },
+        },
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => err,
+        Err(nom::Err::Incomplete(_)) => GrammarError {
+            input: "",
+            expected: Vec::new(),
+        },
+    };
+
+    let start = lead + body.len() - err.input.len();
+    let end = start + err.input.chars().next().map_or(0, char::len_utf8);
+    Err(SyntaxError {
+        span: start..end,
+        expected: err.expected,
+    })
+}
+
+/// Parse an identifier segment: a letter or `_`, then letters, digits or `_`.
+///
+/// Letters and digits are Unicode; so `höhe` and `δ` are identifiers.
+fn segment_parser(input: &str) -> GrammarResult<'_, &str> {
+    recognize(pair(
+        satisfy(|c| c.is_alphabetic() || c == '_'),
+        take_while(|c: char| c.is_alphanumeric() || c == '_'),
+    ))
+    .parse(input)
+}
+
+/// Parse an identifier; dot-separated segments, e.g. `enc.d_model` -> String
+fn ident_parser(input: &str) -> GrammarResult<'_, String> {
+    context(
+        "identifier",
+        map(
+            recognize(pair(
+                segment_parser,
+                many0(preceded(tag("."), segment_parser)),
+            )),
+            String::from,
+        ),
+    )
+    .parse(input)
+}
+
+/// Parse an ellipsis: r"..." -> Ellipsis
+fn ellipsis_parser(input: &str) -> GrammarResult<'_, Component> {
+    map(context("'...'", tag("...")), |_| Component::Ellipsis).parse(input)
+}
+
+/// Parse a dimension: identifier -> Dim
+fn dim_parser(input: &str) -> GrammarResult<'_, Component> {
+    map(ident_parser, Component::Dim).parse(input)
+}
+
+/// Parse a fixed size: r"[0-9]+" -> Literal
+///
+/// A size may not run into an identifier; `3x` is neither a size nor an identifier.
+fn literal_parser(input: &str) -> GrammarResult<'_, Component> {
+    map(
+        context(
+            "size",
+            map_res(
+                terminated(
+                    digit1,
+                    context(
+                        "whitespace",
+                        not(satisfy(|c| c.is_alphanumeric() || c == '_' || c == '.')),
+                    ),
+                ),
+                str::parse::<usize>,
+            ),
+        ),
+        Component::Literal,
+    )
+    .parse(input)
+}
+
+/// Parse a composite dimension: (id1 id2 ...) -> Composite
+///
+/// Once the "(" is seen, errors are fatal; so they are reported inside the composite.
+fn composite_parser(input: &str) -> GrammarResult<'_, Component> {
+    map(
+        delimited(
+            terminated(context("'('", tag("(")), multispace0),
+            cut(separated_list1(multispace1, ident_parser)),
+            cut(preceded(multispace0, context("')'", tag(")")))),
+        ),
+        Component::Composite,
+    )
+    .parse(input)
+}
+
+/// Parse an alias reference: $identifier -> Alias
+fn alias_parser(input: &str) -> GrammarResult<'_, Component> {
+    map(
+        preceded(context("'$'", tag("$")), cut(ident_parser)),
+        Component::Alias,
+    )
+    .parse(input)
+}
+
+/// Parse a single component
+fn component_parser(input: &str) -> GrammarResult<'_, Component> {
+    alt((
+        ellipsis_parser,
+        dim_parser,
+        literal_parser,
+        composite_parser,
+        alias_parser,
+    ))
+    .parse(input)
+}
+
+/// Parse a list of components separated by whitespace
+fn components_parser(input: &str) -> GrammarResult<'_, Vec<Component>> {
+    many1(terminated(component_parser, multispace0)).parse(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ellipsis() {
+        assert_eq!(ellipsis_parser("..."), Ok(("", Component::Ellipsis)));
+        assert_eq!(ellipsis_parser("... "), Ok((" ", Component::Ellipsis)));
+        assert_eq!(ellipsis_parser("...x"), Ok(("x", Component::Ellipsis)));
+    }
+
+    #[test]
+    fn test_identifier() {
+        for prefix in &["_", "a", "A"] {
+            for suffix in &["", "_", "a", "A", "1", "_"] {
+                let id = format!("{prefix}{suffix}");
+                let input = format!("{id} z");
+                assert_eq!(ident_parser(&input), Ok((" z", id)));
+            }
+        }
+
+        for input in &["1", "-", "", ".a"] {
+            assert!(ident_parser(input).is_err());
+        }
+    }
+
+    #[test]
+    fn test_scoped_identifier() {
+        for id in &["enc.d_model", "a.b.c", "höhe", "δ.x1", "_.a"] {
+            let input = format!("{id} z");
+            assert_eq!(ident_parser(&input), Ok((" z", (*id).to_string())));
+        }
+
+        // A trailing or repeated dot is not part of the identifier.
+        assert_eq!(ident_parser("a. z"), Ok((". z", "a".to_string())));
+        assert_eq!(ident_parser("a..b"), Ok(("..b", "a".to_string())));
+        assert_eq!(ident_parser("a.1"), Ok((".1", "a".to_string())));
+    }
+
+    #[test]
+    fn test_dimension() {
+        for id in &["x", "X", "_", "x1", "X1", "_1"] {
+            let input = format!("{id} z");
+            assert_eq!(
+                dim_parser(&input),
+                Ok((" z", Component::Dim((*id).to_string())))
+            );
+        }
+    }
+
+    #[test]
+    fn test_composite() {
+        assert_eq!(
+            composite_parser("(x)"),
+            Ok(("", Component::Composite(vec!["x".to_string()])))
+        );
+        assert_eq!(
+            composite_parser("(x y)"),
+            Ok((
+                "",
+                Component::Composite(vec!["x".to_string(), "y".to_string()])
+            ))
+        );
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(literal_parser("3 x"), Ok((" x", Component::Literal(3))));
+        assert_eq!(literal_parser("512"), Ok(("", Component::Literal(512))));
+        assert_eq!(literal_parser("0)"), Ok((")", Component::Literal(0))));
+        assert_eq!(literal_parser("007"), Ok(("", Component::Literal(7))));
+        assert!(literal_parser("x").is_err());
+        assert!(literal_parser("3x").is_err());
+        assert!(literal_parser("3.5").is_err());
+        assert!(literal_parser("99999999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_alias() {
+        assert_eq!(
+            alias_parser("$img c"),
+            Ok((" c", Component::Alias("img".to_string())))
+        );
+        assert!(alias_parser("img").is_err());
+        assert!(matches!(alias_parser("$ img"), Err(nom::Err::Failure(_))));
+    }
+
+    #[test]
+    fn test_parse_components() {
+        assert_eq!(
+            parse_components(" b ...( x  y ) c "),
+            Ok(vec![
+                Component::Dim("b".to_string()),
+                Component::Ellipsis,
+                Component::Composite(vec!["x".to_string(), "y".to_string()]),
+                Component::Dim("c".to_string())
+            ])
+        );
+        assert_eq!(
+            parse_components("b 3 h"),
+            Ok(vec![
+                Component::Dim("b".to_string()),
+                Component::Literal(3),
+                Component::Dim("h".to_string()),
+            ])
+        );
+        assert_eq!(
+            parse_components("b $img"),
+            Ok(vec![
+                Component::Dim("b".to_string()),
+                Component::Alias("img".to_string()),
+            ])
+        );
+        assert_eq!(
+            parse_components("enc.b ... (dec.h p)"),
+            Ok(vec![
+                Component::Dim("enc.b".to_string()),
+                Component::Ellipsis,
+                Component::Composite(vec!["dec.h".to_string(), "p".to_string()]),
+            ])
+        );
+    }
+
+    fn syntax_error(
+        span: Range<usize>,
+        expected: &[&'static str],
+    ) -> Result<Vec<Component>, SyntaxError> {
+        Err(SyntaxError {
+            span,
+            expected: expected.to_vec(),
+        })
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let any = &["'...'", "identifier", "size", "'('", "'$'"];
+
+        assert_eq!(parse_components(""), syntax_error(0..0, any));
+        assert_eq!(parse_components("  "), syntax_error(2..2, any));
+        assert_eq!(parse_components("b -1"), syntax_error(2..3, any));
+        assert_eq!(parse_components(" b -"), syntax_error(3..4, any));
+        assert_eq!(parse_components("b (h p w"), syntax_error(8..8, &["')'"]));
+        assert_eq!(
+            parse_components("b ( ) c"),
+            syntax_error(4..5, &["identifier"])
+        );
+        assert_eq!(parse_components("b (h 1)"), syntax_error(5..6, &["')'"]));
+        assert_eq!(
+            parse_components("b 3x"),
+            syntax_error(3..4, &["whitespace"])
+        );
+        assert_eq!(
+            parse_components("b $ c"),
+            syntax_error(3..4, &["identifier"])
+        );
+    }
+
+    #[test]
+    fn test_message() {
+        let err = parse_components("b (h p w").unwrap_err();
+        assert_eq!(err.message(), "expected ')'");
+
+        let err = parse_components("b -").unwrap_err();
+        assert_eq!(
+            err.message(),
+            "expected one of '...', identifier, size, '(', '$'"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let input = "b ... (h p w";
+        let err = parse_components(input).unwrap_err();
+        assert_eq!(err.render(input), "b ... (h p w\n            ^");
+
+        assert_eq!(render_caret("a bcd e", &(2..5)), "a bcd e\n  ^^^");
+    }
+}
@@ -0,0 +1,129 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields, LitStr};
+
+/// A field exported as a shape binding.
+struct BindingField {
+    ident: syn::Ident,
+    name: LitStr,
+    ty: syn::Type,
+}
+
+/// Parse the `#[shape_binding(...)]` attributes of a field.
+///
+/// Returns `None` if the field is skipped.
+fn binding_field(field: &syn::Field) -> syn::Result<Option<BindingField>> {
+    let ident = field.ident.clone().expect("named field");
+    let mut name = LitStr::new(&ident.to_string(), ident.span());
+    let mut renamed = false;
+    let mut skip = false;
+
+    for attr in &field.attrs {
+        if !attr.path().is_ident("shape_binding") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else if meta.path.is_ident("rename") {
+                if renamed {
+                    return Err(meta.error("duplicate `rename`"));
+                }
+                name = meta.value()?.parse()?;
+                renamed = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `skip` or `rename = \"...\"`"))
+            }
+        })?;
+    }
+
+    if skip {
+        return Ok(None);
+    }
+    Ok(Some(BindingField {
+        ident,
+        name,
+        ty: field.ty.clone(),
+    }))
+}
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "ShapeBindingSource can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "ShapeBindingSource can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut bindings: Vec<BindingField> = Vec::new();
+    for field in &fields.named {
+        let Some(binding) = binding_field(field)? else {
+            continue;
+        };
+        if let Some(other) = bindings
+            .iter()
+            .find(|b| b.name.value() == binding.name.value())
+        {
+            return Err(syn::Error::new(
+                binding.name.span(),
+                format!(
+                    "duplicate shape binding name \"{}\"; also the name of field `{}`",
+                    binding.name.value(),
+                    other.ident
+                ),
+            ));
+        }
+        bindings.push(binding);
+    }
+
+    let count = bindings.len();
+    let items = bindings.iter().map(|BindingField { ident, name, ty }| {
+        // Spanned on the field type, so non-`usize` fields point at the offender.
+        let value = quote_spanned! { ty.span() => { let value: usize = self.#ident; value } };
+        quote! { (#name, #value) }
+    });
+    let arms = bindings.iter().map(|BindingField { ident, name, .. }| {
+        quote! { #name => ::core::option::Option::Some(self.#ident) }
+    });
+
+    let ident = &input.ident;
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut src_generics = input.generics.clone();
+    src_generics.params.insert(0, syn::parse_quote!('__src));
+    let (impl_generics, _, _) = src_generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::burn_contracts::shapes::bindings::ShapeBindingSource
+            for &'__src #ident #ty_generics #where_clause
+        {
+            type Iter<'__a>
+                = ::core::array::IntoIter<(&'__a str, usize), #count>
+            where
+                Self: '__a;
+
+            fn for_each_shape_binding(&self) -> Self::Iter<'_> {
+                let items: [(&str, usize); #count] = [#(#items),*];
+                items.into_iter()
+            }
+
+            fn lookup_shape_binding(
+                &self,
+                name: &str,
+            ) -> ::core::option::Option<usize> {
+                match name {
+                    #(#arms,)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        }
+    })
+}
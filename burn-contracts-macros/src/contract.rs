@@ -0,0 +1,196 @@
+use crate::pattern;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Expr, FnArg, Ident, ItemFn, LitStr, Pat, ReturnType, Token, Type};
+
+/// One `name = ...` entry of a `#[shape_contract(...)]`.
+enum ContractEntry {
+    /// `arg = "pattern"`, or `return = "pattern"`.
+    Pattern { name: Ident, pattern: LitStr },
+
+    /// `bindings = expr`; a `ShapeBindingSource` seeding the environment.
+    Bindings(Expr),
+}
+
+impl Parse for ContractEntry {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // `return` and `self` are keywords.
+        let name = Ident::parse_any(input)?;
+        input.parse::<Token![=]>()?;
+        if name == "bindings" {
+            Ok(ContractEntry::Bindings(input.parse()?))
+        } else {
+            Ok(ContractEntry::Pattern {
+                name,
+                pattern: input.parse()?,
+            })
+        }
+    }
+}
+
+/// The parsed arguments of a `#[shape_contract(...)]`.
+pub struct ContractArgs {
+    entries: Punctuated<ContractEntry, Token![,]>,
+}
+
+impl Parse for ContractArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            entries: Punctuated::parse_terminated(input)?,
+        })
+    }
+}
+
+/// The names of the arguments which can be checked.
+fn arg_names(item: &ItemFn) -> Vec<Ident> {
+    item.sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) => Some(pat.ident.clone()),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// Expand `#[shape_contract(...)]` on a function.
+pub fn expand(
+    args: ContractArgs,
+    item: ItemFn,
+) -> syn::Result<TokenStream> {
+    if let Some(asyncness) = &item.sig.asyncness {
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "#[shape_contract] is not supported on async functions",
+        ));
+    }
+    let function = item.sig.ident.to_string();
+    let names = arg_names(&item);
+    // Internal names are hygienic; they cannot collide with the function's own.
+    let env = Ident::new("__shape_contract", Span::mixed_site());
+    let result = Ident::new("__shape_contract_result", Span::mixed_site());
+    let source = Ident::new("__shape_contract_bindings", Span::mixed_site());
+
+    let mut seed = None;
+    let mut arg_checks = Vec::new();
+    let mut return_check = None;
+    for entry in args.entries {
+        let (name, pattern) = match entry {
+            ContractEntry::Bindings(expr) => {
+                if seed.is_some() {
+                    return Err(syn::Error::new(expr.span(), "duplicate `bindings`"));
+                }
+                seed = Some(expr);
+                continue;
+            }
+            ContractEntry::Pattern { name, pattern } => (name, pattern),
+        };
+
+        let built = pattern::expand(&pattern)?;
+        let check = |target: &Ident| {
+            let label = name.to_string();
+            quote! {
+                {
+                    static PATTERN: ::std::sync::LazyLock<::burn_contracts::shapes::ShapePattern> =
+                        ::std::sync::LazyLock::new(|| #built);
+                    if let ::core::option::Option::Some(#env) = #env.as_mut() {
+                        #env.check(#label, &PATTERN, &#target);
+                    }
+                }
+            }
+        };
+
+        if name == "return" {
+            if return_check.is_some() {
+                return Err(syn::Error::new(name.span(), "duplicate `return` pattern"));
+            }
+            if matches!(item.sig.output, ReturnType::Default) {
+                return Err(syn::Error::new(
+                    name.span(),
+                    format!("`{function}` does not return a value"),
+                ));
+            }
+            if let ReturnType::Type(_, ty) = &item.sig.output
+                && matches!(**ty, Type::ImplTrait(_))
+            {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "a `return` pattern is not supported on `impl Trait` returns",
+                ));
+            }
+            return_check = Some(check(&result));
+        } else if let Some(arg) = names.iter().find(|arg| **arg == name) {
+            if arg_checks.iter().any(|(seen, _)| seen == arg) {
+                return Err(syn::Error::new(
+                    name.span(),
+                    format!("duplicate pattern for `{name}`"),
+                ));
+            }
+            arg_checks.push((arg.clone(), check(arg)));
+        } else {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("`{name}` is not an argument of `{function}`"),
+            ));
+        }
+    }
+    if arg_checks.is_empty() && return_check.is_none() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "expected `arg = \"pattern\"` entries, such as `x = \"b c h w\"`",
+        ));
+    }
+    let arg_checks = arg_checks.into_iter().map(|(_, check)| check);
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+
+    let body = match return_check {
+        None => quote! { #block },
+        Some(return_check) => {
+            // The body runs in a closure, so `return` and `?` still leave only the body.
+            let ReturnType::Type(_, ty) = &sig.output else {
+                unreachable!("checked above");
+            };
+            quote! {
+                #[allow(clippy::redundant_closure_call)]
+                let #result = (|| -> #ty #block)();
+                #return_check
+                #result
+            }
+        }
+    };
+
+    // The source is bound outside the closure; so `bindings = &...` temporaries live
+    // as long as the environment.
+    let start = quote! {
+        ::burn_contracts::shapes::contract::ContractBindings::if_enabled(#function)
+    };
+    let start = match seed {
+        None => quote! { let mut #env = #start; },
+        Some(expr) => quote! {
+            let #source = #expr;
+            let mut #env = #start.map(|#env| #env.with_bindings(#source));
+        },
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            #start
+            #(#arg_checks)*
+            #body
+        }
+    })
+}
@@ -0,0 +1,69 @@
+//! Procedural macros for `burn-contracts`.
+//!
+//! These macros are re-exported by the `burn-contracts` crate,
+//! and should be used from there.
+use proc_macro::TokenStream;
+use syn::{DeriveInput, ItemFn, LitStr, parse_macro_input};
+
+mod binding_source;
+mod contract;
+mod pattern;
+
+/// Derive `ShapeBindingSource` for a struct with named `usize` fields.
+///
+/// The impl is generated for `&Struct`, matching the other reference sources;
+/// so a config can be passed as `&config` wherever bindings are accepted.
+///
+/// ## Attributes
+///
+/// - `#[shape_binding(rename = "name")]`: bind the field under a different name.
+/// - `#[shape_binding(skip)]`: do not export the field; required for non-`usize` fields.
+#[proc_macro_derive(ShapeBindingSource, attributes(shape_binding))]
+pub fn derive_shape_binding_source(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    binding_source::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Build a `ShapePattern`, validated at compile time.
+///
+/// The pattern is parsed with the same grammar as `ShapePattern::parse()`;
+/// a malformed pattern is a compile error, with a caret at the bad position.
+///
+/// Expands to a `ShapePattern` expression, built without any runtime parsing.
+#[proc_macro]
+pub fn pattern(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as LitStr);
+    pattern::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Build a `RankedPattern`, validated at compile time.
+///
+/// Like `pattern!`, but the arity and ellipsis of the pattern are carried in the type;
+/// so rank mismatches against a tensor are compile errors.
+#[proc_macro]
+pub fn ranked_pattern(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as LitStr);
+    pattern::expand_ranked(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Check the shapes of a function's arguments and return value against patterns.
+///
+/// The checks share one binding environment, seeded by the optional `bindings = ...`
+/// source; and run when `ContractLevel::Shapes` is enabled.
+#[proc_macro_attribute]
+pub fn shape_contract(
+    args: TokenStream,
+    item: TokenStream,
+) -> TokenStream {
+    let args = parse_macro_input!(args as contract::ContractArgs);
+    let item = parse_macro_input!(item as ItemFn);
+    contract::expand(args, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
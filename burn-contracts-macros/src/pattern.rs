@@ -0,0 +1,89 @@
+use burn_contracts_grammar::{Component, parse_components};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+/// Expand `pattern!`, to a `ShapePattern`.
+pub fn expand(input: &LitStr) -> syn::Result<TokenStream> {
+    let components = validated_components(input)?;
+    Ok(build_pattern(&components))
+}
+
+/// Expand `ranked_pattern!`, to a `RankedPattern<ARITY, ELLIPSIS>`.
+pub fn expand_ranked(input: &LitStr) -> syn::Result<TokenStream> {
+    let components = validated_components(input)?;
+    let ellipsis = components.iter().any(|c| matches!(c, Component::Ellipsis));
+    let arity = components.len() - usize::from(ellipsis);
+    let pattern = build_pattern(&components);
+
+    Ok(quote! {
+        ::burn_contracts::shapes::RankedPattern::<#arity, #ellipsis>::from_validated_pattern(#pattern)
+    })
+}
+
+fn validated_components(input: &LitStr) -> syn::Result<Vec<Component>> {
+    let source = input.value();
+
+    let components = parse_components(&source).map_err(|err| {
+        syn::Error::new(
+            input.span(),
+            format!(
+                "invalid shape pattern: {}\n{}",
+                err.message(),
+                err.render(&source)
+            ),
+        )
+    })?;
+
+    if let Some(Component::Alias(name)) =
+        components.iter().find(|c| matches!(c, Component::Alias(_)))
+    {
+        return Err(syn::Error::new(
+            input.span(),
+            format!(
+                "invalid shape pattern \"{source}\": alias \"${name}\" cannot be resolved at compile time; \
+                 use `PatternAliases::parse()`"
+            ),
+        ));
+    }
+
+    if components
+        .iter()
+        .filter(|c| matches!(c, Component::Ellipsis))
+        .count()
+        > 1
+    {
+        return Err(syn::Error::new(
+            input.span(),
+            format!("invalid shape pattern \"{source}\": Only one ellipsis is allowed"),
+        ));
+    }
+
+    Ok(components)
+}
+
+fn build_pattern(components: &[Component]) -> TokenStream {
+    let components = components.iter().map(|component| match component {
+        Component::Dim(id) => quote! {
+            ::burn_contracts::shapes::PatternComponent::Dim(::std::string::String::from(#id))
+        },
+        Component::Ellipsis => quote! {
+            ::burn_contracts::shapes::PatternComponent::Ellipsis
+        },
+        Component::Composite(ids) => quote! {
+            ::burn_contracts::shapes::PatternComponent::Composite(
+                ::std::vec![#(::std::string::String::from(#ids)),*]
+            )
+        },
+        Component::Literal(size) => quote! {
+            ::burn_contracts::shapes::PatternComponent::Literal(#size)
+        },
+        Component::Alias(_) => unreachable!("aliases are rejected by validated_components()"),
+    });
+
+    quote! {
+        ::burn_contracts::shapes::ShapePattern::from_validated_components(
+            ::std::vec![#(#components),*]
+        )
+    }
+}
@@ -4,5 +4,5 @@ set -ex
 
 ./fix.sh
 
-cargo test
+cargo test --workspace
 
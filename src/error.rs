@@ -0,0 +1,145 @@
+//! Errors of the fallible `TensorWrapper` checks.
+use crate::level::ContractLevel;
+use crate::shapes::ShapePatternError;
+
+/// A failed `TensorWrapper` check, from a `try_*` method.
+///
+/// `ShapePatternError`s convert with `?`; so shape matches and checks can share a function.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ContractError {
+    /// From `try_has_dims()`.
+    #[error("Expected tensor to have dimensions {expected:?}, but got {actual:?}")]
+    Dims {
+        expected: Vec<usize>,
+        actual: Vec<usize>,
+    },
+
+    /// From `try_has_named_dims()`.
+    #[error(
+        "Expected dims [{}], found [{}]",
+        format_named(.expected.iter().map(|(n, d)| (n.as_str(), *d))),
+        format_named(.expected.iter().zip(.actual).map(|((n, _), d)| (n.as_str(), *d)))
+    )]
+    NamedDims {
+        expected: Vec<(String, usize)>,
+        actual: Vec<usize>,
+    },
+
+    /// From `try_equals()`; the tensor data, in debug format.
+    #[error("Expected tensor to have data {expected}, but got {actual}")]
+    Data { expected: String, actual: String },
+
+    /// From `try_is_close()`.
+    #[error(
+        "Expected tensor to be within (atol={atol:?}, rtol={rtol:?}) of target\n\
+         - {passed}/{total} ({:.2}%) elements passed",
+        percentage(*.passed, *.total)
+    )]
+    NotClose {
+        atol: f64,
+        rtol: f64,
+        passed: usize,
+        total: usize,
+    },
+
+    /// From `conforms()`; a tensor whose kind or dtype does not conform to a `TensorSpec`.
+    #[error("Tensor of kind {kind} ({dtype}) does not conform to \"{spec}\"")]
+    ElementMismatch {
+        spec: String,
+        kind: String,
+        dtype: String,
+    },
+
+    /// From a shape pattern match.
+    #[error(transparent)]
+    Shape(#[from] ShapePatternError),
+}
+
+fn format_named<'a>(dims: impl Iterator<Item = (&'a str, usize)>) -> String {
+    dims.map(|(n, d)| format!("{n}={d}"))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn percentage(
+    passed: usize,
+    total: usize,
+) -> f64 {
+    (passed as f64 / total as f64) * 100.0
+}
+
+impl ContractError {
+    /// Render the error with its context; see `ShapePatternError::render()`.
+    #[must_use]
+    pub fn render(&self) -> String {
+        match self {
+            ContractError::Shape(err) => err.render(),
+            err => err.to_string(),
+        }
+    }
+
+    /// The name of the failed check, such as `has_dims`.
+    #[must_use]
+    pub fn check(&self) -> &'static str {
+        match self {
+            ContractError::Dims { .. } => "has_dims",
+            ContractError::NamedDims { .. } => "has_named_dims",
+            ContractError::Data { .. } => "equals",
+            ContractError::NotClose { .. } => "is_close",
+            ContractError::ElementMismatch { .. } => "conforms",
+            ContractError::Shape(_) => "shape",
+        }
+    }
+
+    /// The level of the failed check.
+    #[must_use]
+    pub fn level(&self) -> ContractLevel {
+        match self {
+            ContractError::Dims { .. }
+            | ContractError::NamedDims { .. }
+            | ContractError::ElementMismatch { .. }
+            | ContractError::Shape(_) => ContractLevel::Shapes,
+            ContractError::NotClose { .. } => ContractLevel::CheapValues,
+            ContractError::Data { .. } => ContractLevel::FullValues,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let err = ContractError::NamedDims {
+            expected: vec![("rows".to_string(), 1), ("cols".to_string(), 2)],
+            actual: vec![2, 1],
+        };
+        assert_eq!(
+            err.to_string(),
+            "Expected dims [rows=1, cols=2], found [rows=2, cols=1]"
+        );
+        assert_eq!(err.check(), "has_named_dims");
+
+        let err = ContractError::NotClose {
+            atol: 1e-8,
+            rtol: 1e-5,
+            passed: 3,
+            total: 4,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Expected tensor to be within (atol=1e-8, rtol=1e-5) of target\n\
+             - 3/4 (75.00%) elements passed"
+        );
+        assert_eq!(err.level(), ContractLevel::CheapValues);
+
+        let err: ContractError = ShapePatternError::InvalidPattern {
+            pattern: "b".to_string(),
+            message: "bad".to_string(),
+        }
+        .into();
+        assert_eq!(err.to_string(), "Invalid pattern \"b\": bad");
+    }
+}
@@ -0,0 +1,234 @@
+//! Contract enforcement levels.
+//!
+//! Every `TensorWrapper` assertion, and every `#[shape_contract]`, has a cost level;
+//! and only runs when the current `contract_level()` is at least that level.
+//!
+//! The level is capped at compile time by cargo features: `contracts-shapes`,
+//! `contracts-cheap-values` and `contracts-full-values`, the default, each of which
+//! enables the ones below it. Checks above the cap compile to nothing. Shape checks are
+//! always compiled in; without any of the features, the cap is `ContractLevel::Shapes`.
+//! Only a runtime choice turns them off.
+//!
+//! Below the cap, the level is chosen at runtime by `set_contract_level()`, or the
+//! `BURN_CONTRACTS_LEVEL` environment variable (`off`, `shapes`, `cheap-values` or
+//! `full-values`), read on first use; and overridden on a thread by `with_contract_level()`.
+//! An unknown `BURN_CONTRACTS_LEVEL` is logged, and ignored.
+use std::cell::Cell;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The environment variable selecting the runtime contract level.
+pub const CONTRACT_LEVEL_ENV: &str = "BURN_CONTRACTS_LEVEL";
+
+/// How much contract checking to do; each level includes the ones below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum ContractLevel {
+    /// No checks.
+    Off = 0,
+
+    /// Checks of tensor metadata: shapes, kinds and dtypes. Never reads tensor data.
+    Shapes = 1,
+
+    /// Checks which reduce tensor data on the device, reading back only a scalar;
+    /// such as `is_close()`.
+    CheapValues = 2,
+
+    /// Checks which read back whole tensors; such as `equals()`.
+    FullValues = 3,
+}
+
+/// The level cap selected by cargo features; at least `ContractLevel::Shapes`.
+pub const MAX_CONTRACT_LEVEL: ContractLevel = if cfg!(feature = "contracts-full-values") {
+    ContractLevel::FullValues
+} else if cfg!(feature = "contracts-cheap-values") {
+    ContractLevel::CheapValues
+} else {
+    ContractLevel::Shapes
+};
+
+/// The names of the levels, as accepted by `ContractLevel::from_str()`.
+const LEVEL_NAMES: &[(&str, ContractLevel)] = &[
+    ("off", ContractLevel::Off),
+    ("shapes", ContractLevel::Shapes),
+    ("cheap-values", ContractLevel::CheapValues),
+    ("full-values", ContractLevel::FullValues),
+];
+
+/// An unrecognized contract level name.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "Unknown contract level \"{0}\"; expected one of \"off\", \"shapes\", \"cheap-values\" or \"full-values\""
+)]
+pub struct InvalidContractLevel(pub String);
+
+impl ContractLevel {
+    /// The level name, e.g. `cheap-values`.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        LEVEL_NAMES[*self as usize].0
+    }
+
+    /// Whether checks of this level run, at the current `contract_level()`.
+    ///
+    /// Levels above `MAX_CONTRACT_LEVEL` are never enabled; a constant the compiler folds.
+    #[must_use]
+    #[inline]
+    pub fn is_enabled(self) -> bool {
+        self <= MAX_CONTRACT_LEVEL && self <= contract_level()
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        LEVEL_NAMES.get(usize::from(value)).map(|(_, level)| *level)
+    }
+}
+
+impl FromStr for ContractLevel {
+    type Err = InvalidContractLevel;
+
+    /// Parse a level name; case-insensitive, with `_` for `-`.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let name = input.trim().to_ascii_lowercase().replace('_', "-");
+        LEVEL_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, level)| *level)
+            .ok_or_else(|| InvalidContractLevel(input.to_string()))
+    }
+}
+
+impl Display for ContractLevel {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The runtime level; `UNSET` until set, or read from the environment.
+static LEVEL: AtomicU8 = AtomicU8::new(UNSET);
+const UNSET: u8 = u8::MAX;
+
+thread_local! {
+    static OVERRIDE: Cell<Option<ContractLevel>> = const { Cell::new(None) };
+}
+
+/// The runtime level from the environment; `MAX_CONTRACT_LEVEL` if unset.
+///
+/// An unknown level is logged as a `log` error, and also falls back to `MAX_CONTRACT_LEVEL`;
+/// a misconfiguration should not turn checks off.
+fn env_contract_level() -> ContractLevel {
+    level_from_env(std::env::var(CONTRACT_LEVEL_ENV).ok().as_deref())
+}
+
+fn level_from_env(value: Option<&str>) -> ContractLevel {
+    value.map_or(MAX_CONTRACT_LEVEL, |value| {
+        value.parse().unwrap_or_else(|err| {
+            log::error!(
+                target: "burn_contracts",
+                "{CONTRACT_LEVEL_ENV}: {err}; using \"{MAX_CONTRACT_LEVEL}\""
+            );
+            MAX_CONTRACT_LEVEL
+        })
+    })
+}
+
+/// Set the process-wide contract level; capped at `MAX_CONTRACT_LEVEL`.
+///
+/// Takes precedence over `BURN_CONTRACTS_LEVEL`.
+pub fn set_contract_level(level: ContractLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Get the current contract level.
+///
+/// This is the level of the innermost `with_contract_level()` on this thread; or the
+/// process-wide level, from `set_contract_level()` or `BURN_CONTRACTS_LEVEL`.
+/// It never exceeds `MAX_CONTRACT_LEVEL`.
+#[must_use]
+pub fn contract_level() -> ContractLevel {
+    let level = OVERRIDE.with(Cell::get).unwrap_or_else(|| {
+        ContractLevel::from_u8(LEVEL.load(Ordering::Relaxed)).unwrap_or_else(|| {
+            let level = env_contract_level();
+            // Racing first reads agree; an explicit `set_contract_level()` wins.
+            let _ =
+                LEVEL.compare_exchange(UNSET, level as u8, Ordering::Relaxed, Ordering::Relaxed);
+            level
+        })
+    });
+    level.min(MAX_CONTRACT_LEVEL)
+}
+
+/// Run `f` with the contract level overridden on this thread.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::level::{ContractLevel, contract_level, with_contract_level};
+///
+/// with_contract_level(ContractLevel::Shapes, || {
+///     assert!(contract_level() <= ContractLevel::Shapes);
+///     assert!(!ContractLevel::FullValues.is_enabled());
+/// });
+/// ```
+pub fn with_contract_level<R>(
+    level: ContractLevel,
+    f: impl FnOnce() -> R,
+) -> R {
+    struct Restore(Option<ContractLevel>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            OVERRIDE.with(|o| o.set(self.0));
+        }
+    }
+
+    let _restore = Restore(OVERRIDE.with(|o| o.replace(Some(level))));
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        for (name, level) in LEVEL_NAMES {
+            assert_eq!(name.parse::<ContractLevel>().unwrap(), *level);
+            assert_eq!(level.to_string(), *name);
+        }
+        assert_eq!(
+            " Cheap_Values".parse::<ContractLevel>().unwrap(),
+            ContractLevel::CheapValues
+        );
+        assert_eq!(
+            "all".parse::<ContractLevel>().unwrap_err(),
+            InvalidContractLevel("all".to_string())
+        );
+    }
+
+    #[test]
+    fn test_level_from_env() {
+        assert_eq!(level_from_env(None), MAX_CONTRACT_LEVEL);
+        assert_eq!(level_from_env(Some("off")), ContractLevel::Off);
+        assert_eq!(level_from_env(Some("everything")), MAX_CONTRACT_LEVEL);
+    }
+
+    #[test]
+    fn test_with_contract_level() {
+        assert!(ContractLevel::Off < ContractLevel::Shapes);
+        assert!(ContractLevel::CheapValues < ContractLevel::FullValues);
+
+        with_contract_level(ContractLevel::Off, || {
+            assert!(!ContractLevel::Shapes.is_enabled());
+            with_contract_level(ContractLevel::CheapValues, || {
+                assert_eq!(
+                    contract_level(),
+                    ContractLevel::CheapValues.min(MAX_CONTRACT_LEVEL)
+                );
+            });
+            assert_eq!(contract_level(), ContractLevel::Off);
+        });
+    }
+}
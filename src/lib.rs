@@ -1,11 +1,121 @@
+// Lets `burn_contracts_macros` expansions name `::burn_contracts` from inside this crate.
+extern crate self as burn_contracts;
+
+mod error;
+pub mod level;
 pub mod shapes;
+mod soft;
 #[cfg(any(test, feature = "testing"))]
 pub mod testing;
+pub mod violation;
+
+/// Build a `ShapePattern`, validated at compile time.
+///
+/// The pattern is parsed with the same grammar as `ShapePattern::parse()`;
+/// a malformed pattern is a compile error, with a caret at the bad position.
+///
+/// Expands to a `ShapePattern` expression, built without any runtime parsing;
+/// wrap it in a `LazyLock` to share one instance.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::pattern;
+/// use burn_contracts::shapes::ShapePattern;
+///
+/// assert_eq!(
+///     pattern!("b ... (h p) c"),
+///     ShapePattern::parse("b ... (h p) c").unwrap(),
+/// );
+/// ```
+///
+/// ```compile_fail
+/// // error: invalid shape pattern: expected ')'
+/// //        b ... (h p w
+/// //                    ^
+/// let p = burn_contracts::pattern!("b ... (h p w");
+/// ```
+pub use burn_contracts_macros::pattern;
+
+/// Build a `RankedPattern`, validated at compile time.
+///
+/// Like `pattern!`, but the arity and ellipsis of the pattern are carried in the type;
+/// so `TensorWrapper::unpacks_pattern()` rejects rank mismatches at compile time.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::ranked_pattern;
+/// use burn_contracts::shapes::RankedPattern;
+///
+/// let fixed: RankedPattern<3, false> = ranked_pattern!("b (h p) c");
+/// let open: RankedPattern<2, true> = ranked_pattern!("b ... c");
+/// ```
+pub use burn_contracts_macros::ranked_pattern;
 
+/// Check the shapes of a function's arguments and return value against patterns.
+///
+/// Each `arg = "pattern"` is checked on entry, and `return = "pattern"` on exit;
+/// all in one binding environment, so a dimension bound by one argument must agree
+/// with every other use of it. `bindings = expr` seeds the environment from any
+/// `ShapeBindingSource`, such as a config; lookup-only and scoped sources included.
+///
+/// Patterns are validated at compile time, as with `pattern!`. Checked values implement
+/// `ContractShape`; tensors and references to them, and `Option` and `Result` values,
+/// which are checked only when they hold one.
+///
+/// The checks are `ContractLevel::Shapes` checks; they are skipped when the runtime
+/// level is `ContractLevel::Off`.
+/// With a `return` pattern, the body runs in a closure, so `return` and `?` are checked
+/// too; as the closure must name the return type, `impl Trait` returns are rejected.
+///
+/// ## Panics
+///
+/// A checked shape which does not match its pattern is reported as a `Violation`;
+/// the default `PanicHandler` panics.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn::backend::NdArray;
+/// use burn::prelude::Backend;
+/// use burn::tensor::Tensor;
+/// use burn::tensor::module::conv2d;
+/// use burn::tensor::ops::ConvOptions;
+/// use burn_contracts::shape_contract;
+///
+/// #[shape_contract(x = "b c h w", w = "k c 3 3", return = "b k h w")]
+/// fn same_conv<B: Backend>(x: Tensor<B, 4>, w: Tensor<B, 4>) -> Tensor<B, 4> {
+///     conv2d(x, w, None, ConvOptions::new([1, 1], [1, 1], [1, 1], 1))
+/// }
+///
+/// let device = Default::default();
+/// let x = Tensor::<NdArray, 4>::zeros([2, 3, 8, 8], &device);
+/// let w = Tensor::<NdArray, 4>::zeros([16, 3, 3, 3], &device);
+/// assert_eq!(same_conv(x, w).dims(), [2, 16, 8, 8]);
+/// ```
+///
+/// ```compile_fail
+/// // error: `y` is not an argument of `f`
+/// #[burn_contracts::shape_contract(y = "b c")]
+/// fn f(x: usize) {}
+/// ```
+///
+/// ```compile_fail
+/// // error: a `return` pattern is not supported on `impl Trait` returns
+/// #[burn_contracts::shape_contract(return = "b c")]
+/// fn f() -> impl Clone {}
+/// ```
+pub use burn_contracts_macros::shape_contract;
+
+use crate::level::ContractLevel;
 use crate::shapes::ShapePatternError;
+use crate::violation::{Violation, report_violation};
 use burn::prelude::{Backend, Float};
-use burn::tensor::{BasicOps, Tensor};
-use shapes::ShapePattern;
+use burn::tensor::{BasicOps, Tensor, TensorKind};
+pub use error::ContractError;
+use shapes::{PatternHandle, ShapeMatch, ShapePattern, TensorSpec};
+pub use soft::{SoftAssertions, soft_assert};
 
 /// A wrapper around a Tensor that provides additional assertions.
 #[derive(Clone, Debug)]
@@ -33,13 +143,16 @@ where
 {
     /// Assert that the wrapped tensor has the expected dimensions.
     ///
+    /// A `ContractLevel::Shapes` check.
+    ///
     /// ## Parameters
     ///
     /// - `dims`: The expected dimensions of the tensor.
     ///
     /// ## Panics
     ///
-    /// Panics if the tensor does not have the expected dimensions.
+    /// Reports a `Violation` if the tensor does not have the expected dimensions;
+    /// the default `PanicHandler` panics.
     ///
     /// ## Example:
     /// ```
@@ -53,23 +166,63 @@ where
     /// assert_tensor(&tensor).has_dims([2, 2]);
     /// ```
     #[allow(clippy::must_use_candidate)]
+    #[track_caller]
     pub fn has_dims(
         &self,
         dims: [usize; D],
     ) -> &Self {
-        // Example assertion
-        assert_eq!(
-            self.inner.dims(),
-            dims,
-            "Expected tensor to have dimensions {:?}, but got {:?}",
-            dims,
-            self.inner.dims()
-        );
+        if let Err(err) = self.try_has_dims(dims) {
+            report_violation(&Violation::from_error(&err));
+        }
         self
     }
 
+    /// Check that the wrapped tensor has the expected dimensions.
+    ///
+    /// The fallible form of `has_dims()`; a `ContractLevel::Shapes` check.
+    /// When that level is not enabled, such as with `BURN_CONTRACTS_LEVEL=off`,
+    /// the check is skipped, and `Ok` means "not checked".
+    ///
+    /// ## Parameters
+    ///
+    /// - `dims`: The expected dimensions of the tensor.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `ContractError::Dims` if the tensor does not have the expected dimensions.
+    ///
+    /// ## Example:
+    /// ```
+    /// use burn::backend::NdArray;
+    /// use burn::tensor::Tensor;
+    /// use burn_contracts::{ContractError, assert_tensor};
+    ///
+    /// fn batch<B: burn::prelude::Backend>(x: &Tensor<B, 3>) -> Result<usize, ContractError> {
+    ///     let [b] = assert_tensor(x).unpacks_shape(["b"], "b t d", &[("d", 4)])?;
+    ///     assert_tensor(x).try_has_dims([b, 8, 4])?;
+    ///     Ok(b)
+    /// }
+    ///
+    /// let device = Default::default();
+    /// assert_eq!(batch(&Tensor::<NdArray, 3>::zeros([2, 8, 4], &device)).unwrap(), 2);
+    /// ```
+    pub fn try_has_dims(
+        &self,
+        dims: [usize; D],
+    ) -> Result<&Self, ContractError> {
+        if ContractLevel::Shapes.is_enabled() && self.inner.dims() != dims {
+            return Err(ContractError::Dims {
+                expected: dims.to_vec(),
+                actual: self.inner.dims().to_vec(),
+            });
+        }
+        Ok(self)
+    }
+
     /// Unpacks components of the shape of the tensor according to a pattern.
     ///
+    /// Runs at every `ContractLevel`; the unpacked values are needed either way.
+    ///
     /// ## Parameters
     ///
     /// - `keys`: The keys to select from the unpacked shape.
@@ -119,20 +272,152 @@ where
         pattern: &str,
         bindings: C,
     ) -> Result<[usize; S], ShapePatternError> {
-        Ok(ShapePattern::cached_parse(pattern)?
+        self.unpacks_pattern(keys, &*ShapePattern::cached_parse(pattern)?, bindings)
+    }
+
+    /// Unpacks components of the shape of the tensor according to a parsed pattern.
+    ///
+    /// Like `unpacks_shape()`, but skips the parse cache lookup;
+    /// for use with a held pattern, such as a `LazyShapePattern` static.
+    ///
+    /// Patterns with a known rank, such as `RankedPattern`, are checked
+    /// against the tensor rank `D` at compile time.
+    ///
+    /// ## Parameters
+    ///
+    /// - `keys`: The keys to select from the unpacked shape.
+    /// - `pattern`: The pattern to unpack the shape.
+    /// - `bindings`: The bindings to use for the unpacking.
+    ///
+    /// ## Returns
+    ///
+    /// The unpacked shape.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the bindings do not match the shape.
+    ///
+    /// A `RankedPattern` which cannot match rank `D` fails to compile:
+    ///
+    /// ```compile_fail
+    /// use burn::backend::NdArray;
+    /// use burn::tensor::Tensor;
+    /// use burn_contracts::{assert_tensor, ranked_pattern};
+    ///
+    /// let device = Default::default();
+    /// let tensor = Tensor::<NdArray, 4>::zeros([2, 3, 4, 5], &device);
+    ///
+    /// let [b] = assert_tensor(&tensor)
+    ///     .unpacks_pattern(["b"], &ranked_pattern!("b h w"), &[("h", 3)])
+    ///     .unwrap();
+    /// ```
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use burn::backend::NdArray;
+    /// use burn::tensor::Tensor;
+    /// use burn_contracts::assert_tensor;
+    /// use burn_contracts::shapes::LazyShapePattern;
+    ///
+    /// static PATCHES: LazyShapePattern = LazyShapePattern::new("b ... (h p) (w p) c");
+    ///
+    /// let device = Default::default();
+    /// let tensor = Tensor::<NdArray, 4>::zeros([2, 5 * 4, 4 * 4, 3], &device);
+    ///
+    /// let [h, w] = assert_tensor(&tensor)
+    ///     .unpacks_pattern(["h", "w"], &PATCHES, &[("p", 4)])
+    ///     .unwrap();
+    /// assert_eq!((h, w), (5, 4));
+    /// ```
+    pub fn unpacks_pattern<const S: usize, P, C>(
+        &self,
+        keys: [&str; S],
+        pattern: &P,
+        bindings: C,
+    ) -> Result<[usize; S], ShapePatternError>
+    where
+        P: PatternHandle + ?Sized,
+        C: shapes::ShapeBindingSource,
+    {
+        const {
+            assert!(
+                shapes::ranked::accepts_rank::<P>(D),
+                "pattern rank is incompatible with the tensor rank"
+            );
+        };
+
+        Ok(pattern
+            .shape_pattern()
             .match_bindings(&self.inner.dims(), bindings)?
             .select(keys))
     }
 
+    /// Check the wrapped tensor against a `TensorSpec`; both its element type and its shape.
+    ///
+    /// Runs at every `ContractLevel`, as it returns the `ShapeMatch`.
+    ///
+    /// ## Parameters
+    ///
+    /// - `spec`: The spec, such as `"Float[b c h w]"`.
+    /// - `bindings`: The bindings to use for matching the shape.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `ContractError::ElementMismatch` if the tensor kind or dtype does not conform
+    /// to the spec; or `ContractError::Shape` if the shape does not match the spec's pattern.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the `ShapeMatch` of the tensor shape.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use burn::backend::NdArray;
+    /// use burn::tensor::{Bool, Tensor};
+    /// use burn_contracts::assert_tensor;
+    /// use burn_contracts::shapes::TensorSpec;
+    ///
+    /// let device = Default::default();
+    /// let mask = Tensor::<NdArray, 4, Bool>::empty([2, 1, 5, 5], &device);
+    ///
+    /// let spec = TensorSpec::parse("Bool[b 1 t t]").unwrap();
+    /// let [t] = assert_tensor(&mask).conforms(&spec, &[("b", 2)]).unwrap().select(["t"]);
+    /// assert_eq!(t, 5);
+    ///
+    /// let spec = TensorSpec::parse("Float[b 1 t t]").unwrap();
+    /// assert!(assert_tensor(&mask).conforms(&spec, &[("b", 2)]).is_err());
+    /// ```
+    pub fn conforms<C: shapes::ShapeBindingSource>(
+        &self,
+        spec: &TensorSpec,
+        bindings: C,
+    ) -> Result<ShapeMatch, ContractError> {
+        let kind = <K as TensorKind<B>>::name();
+        let dtype = self.inner.dtype();
+        if !spec.element.accepts(kind, dtype) {
+            return Err(ContractError::ElementMismatch {
+                spec: spec.to_string(),
+                kind: kind.to_string(),
+                dtype: format!("{dtype:?}"),
+            });
+        }
+        Ok(spec.pattern.match_bindings(&self.inner.dims(), bindings)?)
+    }
+
     /// Assert that the wrapped tensor has the expected named dimensions.
     ///
+    /// A `ContractLevel::Shapes` check.
+    ///
     /// ## Parameters
     ///
     /// - `dims`: The expected named dimensions of the tensor.
     ///
     /// ## Panics
     ///
-    /// Panics if the tensor does not have the expected named dimensions.
+    /// Reports a `Violation` if the tensor does not have the expected named dimensions;
+    /// the default `PanicHandler` panics.
     ///
     /// ## Example:
     /// ```
@@ -146,36 +431,44 @@ where
     /// assert_tensor(&tensor).has_named_dims([("rows", 2), ("cols", 2)]);
     /// ```
     #[allow(clippy::must_use_candidate)]
+    #[track_caller]
     pub fn has_named_dims(
         &self,
         dims: [(&str, usize); D],
     ) -> &Self {
-        if self
-            .inner
-            .dims()
-            .iter()
-            .zip(dims.iter())
-            .all(|(&a, &(_, b))| a == b)
-        {
-            return self;
+        if let Err(err) = self.try_has_named_dims(dims) {
+            report_violation(&Violation::from_error(&err));
         }
+        self
+    }
 
-        let actual = self
-            .inner
-            .dims()
-            .iter()
-            .zip(dims.iter())
-            .map(|(&d, &(n, _))| format!("{n}={d}"))
-            .collect::<Vec<String>>()
-            .join(", ");
-
-        let expected = dims
-            .iter()
-            .map(|&(n, d)| format!("{n}={d}"))
-            .collect::<Vec<String>>()
-            .join(", ");
-
-        panic!("Expected dims [{expected}], found [{actual}]")
+    /// Check that the wrapped tensor has the expected named dimensions.
+    ///
+    /// The fallible form of `has_named_dims()`; a `ContractLevel::Shapes` check.
+    /// When that level is not enabled, the check is skipped, and `Ok` means "not checked".
+    ///
+    /// ## Parameters
+    ///
+    /// - `dims`: The expected named dimensions of the tensor.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `ContractError::NamedDims` if the tensor does not have the expected
+    /// named dimensions.
+    pub fn try_has_named_dims(
+        &self,
+        dims: [(&str, usize); D],
+    ) -> Result<&Self, ContractError> {
+        let actual = self.inner.dims();
+        if ContractLevel::Shapes.is_enabled()
+            && !actual.iter().zip(dims.iter()).all(|(&a, &(_, b))| a == b)
+        {
+            return Err(ContractError::NamedDims {
+                expected: dims.iter().map(|&(n, d)| (n.to_string(), d)).collect(),
+                actual: actual.to_vec(),
+            });
+        }
+        Ok(self)
     }
 }
 #[cfg(test)]
@@ -206,6 +499,17 @@ mod tests {
         assert_eq!(h, 5);
         assert_eq!(w, 4);
 
+        // Deref-coerced pattern strings.
+        let owned = String::from("b ... c");
+        let cow = std::borrow::Cow::Borrowed("b ... c");
+        let field: &&str = &"b ... c";
+        let [c] = assert_tensor(&tensor).unpacks_shape(["c"], &owned, &[("b", 2)])?;
+        assert_eq!(c, 3);
+        let [c] = assert_tensor(&tensor).unpacks_shape(["c"], &cow, &[("b", 2)])?;
+        assert_eq!(c, 3);
+        let [c] = assert_tensor(&tensor).unpacks_shape(["c"], field, &[("b", 2)])?;
+        assert_eq!(c, 3);
+
         Ok(())
     }
 
@@ -258,4 +562,158 @@ mod tests {
 
         assert_tensor(&tensor).has_named_dims([("rows", 1), ("cols", 2)]);
     }
+
+    #[test]
+    fn test_conforms() {
+        impl_test_conforms::<NdArray>();
+    }
+
+    fn impl_test_conforms<B: Backend>() {
+        let device = Default::default();
+        let floats = Tensor::<B, 3>::zeros([2, 3, 4], &device);
+        let ints = Tensor::<B, 2, burn::tensor::Int>::zeros([2, 5], &device);
+
+        let spec = TensorSpec::parse("Float[b 3 w]").unwrap();
+        let m = assert_tensor(&floats).conforms(&spec, &[("b", 2)]).unwrap();
+        assert_eq!(m.select(["w"]), [4]);
+        assert!(assert_tensor(&floats).conforms(&spec, &[("b", 3)]).is_err());
+
+        let spec = TensorSpec::parse("Shaped[b t]").unwrap();
+        assert!(assert_tensor(&ints).conforms(&spec, &[("b", 2)]).is_ok());
+
+        let spec = TensorSpec::parse("Float[b t]").unwrap();
+        let err = assert_tensor(&ints)
+            .conforms(&spec, &[("b", 2)])
+            .unwrap_err();
+        let ContractError::ElementMismatch { kind, .. } = &err else {
+            panic!("expected an ElementMismatch: {err}");
+        };
+        assert_eq!(kind, "Int");
+    }
+
+    #[shape_contract(
+        x = "b (h p)",
+        bindings = &crate::shapes::bindings::from_fn(|n| (n == "p").then_some(2)),
+        return = "b h"
+    )]
+    fn halve<B: Backend>(x: &Tensor<B, 2>) -> Tensor<B, 2> {
+        let [b, n] = x.dims();
+        x.clone()
+            .reshape([b, n / 2, 2])
+            .sum_dim(2)
+            .reshape([b, n / 2])
+    }
+
+    #[shape_contract(x = "b h", return = "h b")]
+    fn fallible<B: Backend>(
+        x: Tensor<B, 2>,
+        transpose: bool,
+    ) -> Result<Tensor<B, 2>, String> {
+        if x.dims()[0] == 0 {
+            return Err("empty".to_string());
+        }
+        Ok(if transpose { x.transpose() } else { x })
+    }
+
+    #[test]
+    fn test_shape_contract() {
+        let device = Default::default();
+        let x = Tensor::<NdArray, 2>::zeros([3, 8], &device);
+        assert_eq!(halve(&x).dims(), [3, 4]);
+
+        assert!(fallible(x.clone(), true).is_ok());
+        let empty = Tensor::<NdArray, 2>::zeros([0, 8], &device);
+        assert!(fallible(empty, false).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape contract of `halve` violated by `x`")]
+    fn test_shape_contract_argument() {
+        let device = Default::default();
+        halve(&Tensor::<NdArray, 2>::zeros([3, 7], &device));
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape contract of `fallible` violated by `return`")]
+    fn test_shape_contract_return() {
+        let device = Default::default();
+        let _ = fallible(Tensor::<NdArray, 2>::zeros([3, 8], &device), false);
+    }
+
+    #[test]
+    fn test_contract_levels() {
+        let device = Default::default();
+        let x = Tensor::<NdArray, 2>::zeros([3, 7], &device);
+
+        crate::level::with_contract_level(ContractLevel::Off, || {
+            assert_tensor(&x)
+                .has_dims([1, 2])
+                .has_named_dims([("rows", 1), ("cols", 2)]);
+            assert!(fallible(x.clone(), false).is_ok());
+
+            // Unpacking still matches; its results are needed.
+            assert!(
+                assert_tensor(&x)
+                    .unpacks_shape(["b"], "b", &[("b", 3)])
+                    .is_err()
+            );
+        });
+    }
+
+    #[test]
+    fn test_violation_handler() {
+        use crate::violation::{ReportHandler, with_violation_handler};
+        use std::sync::Arc;
+
+        let device = Default::default();
+        let x = Tensor::<NdArray, 2>::zeros([3, 7], &device);
+
+        let report = Arc::new(ReportHandler::default());
+        with_violation_handler(report.clone(), || {
+            assert_tensor(&x)
+                .has_dims([1, 2])
+                .has_named_dims([("rows", 3), ("cols", 7)])
+                .has_named_dims([("rows", 1), ("cols", 7)]);
+            assert!(fallible(x.clone(), false).is_ok());
+        });
+
+        let checks: Vec<_> = report.take().into_iter().map(|v| v.check).collect();
+        assert_eq!(checks, ["has_dims", "has_named_dims", "shape_contract"]);
+    }
+
+    #[test]
+    fn test_try_dims() {
+        let device = Default::default();
+        let x = Tensor::<NdArray, 2>::zeros([2, 1], &device);
+
+        assert!(assert_tensor(&x).try_has_dims([2, 1]).is_ok());
+        assert_eq!(
+            assert_tensor(&x).try_has_dims([1, 2]).unwrap_err(),
+            ContractError::Dims {
+                expected: vec![1, 2],
+                actual: vec![2, 1],
+            }
+        );
+
+        let err = assert_tensor(&x)
+            .try_has_named_dims([("rows", 2), ("cols", 1)])
+            .and_then(|x| x.try_has_named_dims([("rows", 1), ("cols", 2)]))
+            .unwrap_err();
+        assert_eq!(err.check(), "has_named_dims");
+        assert_eq!(
+            err.to_string(),
+            "Expected dims [rows=1, cols=2], found [rows=2, cols=1]"
+        );
+    }
+
+    #[test]
+    fn test_unpacks_empty_batch() {
+        let device = Default::default();
+        let x = Tensor::<NdArray, 2>::zeros([0, 4], &device);
+
+        let [b, t, d] = assert_tensor(&x)
+            .unpacks_shape(["b", "t", "d"], "(b t) d", &[("b", 0)])
+            .unwrap();
+        assert_eq!([b, t, d], [0, 0, 4]);
+    }
 }
@@ -0,0 +1,546 @@
+//! Operations which build new `ShapePattern`s from existing ones.
+use crate::shapes::exp::{PatternComponent, ShapePattern, ShapePatternError};
+use std::collections::{BTreeMap, HashMap};
+
+/// The result of `ShapePattern::unify()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unifier {
+    /// The unified pattern; matched by shapes which match both patterns.
+    pub pattern: ShapePattern,
+
+    /// The names which were solved in terms of other names,
+    /// e.g. `d` -> `(h k)` when unifying `"b d"` with `"b (h k)"`.
+    pub substitution: BTreeMap<String, PatternComponent>,
+}
+
+/// A pair of aligned components, or a component matched only by the other pattern's ellipsis.
+enum Slot<'a> {
+    Pair(&'a PatternComponent, &'a PatternComponent),
+    Single(&'a PatternComponent),
+    Ellipsis,
+}
+
+/// Split components around the ellipsis: `(prefix, suffix, has_ellipsis)`.
+fn split(pattern: &ShapePattern) -> (&[PatternComponent], &[PatternComponent], bool) {
+    let components = pattern.components();
+    match pattern.ellipsis_pos() {
+        Some(pos) => (&components[..pos], &components[pos + 1..], true),
+        None => (components, &[], false),
+    }
+}
+
+/// An axis size, as a product of names and a constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Term {
+    names: Vec<String>,
+    size: usize,
+}
+
+impl Term {
+    fn of(component: &PatternComponent) -> Self {
+        let (names, size) = match component {
+            PatternComponent::Dim(id) => (vec![id.clone()], 1),
+            PatternComponent::Composite(ids) => (ids.clone(), 1),
+            PatternComponent::Literal(size) => (Vec::new(), *size),
+            PatternComponent::Ellipsis => (Vec::new(), 1),
+        };
+        Self { names, size }
+    }
+
+    /// The component for this term; `None` if it has no pattern representation,
+    /// i.e. names scaled by a constant.
+    fn component(
+        &self,
+        composite: bool,
+    ) -> Option<PatternComponent> {
+        match (self.names.as_slice(), self.size) {
+            ([], size) => Some(PatternComponent::Literal(size)),
+            ([id], 1) if !composite => Some(PatternComponent::Dim(id.clone())),
+            (ids, 1) => Some(PatternComponent::Composite(ids.to_vec())),
+            _ => None,
+        }
+    }
+}
+
+/// Solutions for names, as terms over other names.
+#[derive(Default)]
+struct Substitution {
+    solved: HashMap<String, Term>,
+}
+
+/// An outcome of unifying two terms.
+enum Unified {
+    Ok,
+    /// No sizes satisfy both terms.
+    Incompatible,
+}
+
+impl Substitution {
+    /// Expand a term to unsolved names.
+    fn resolve(
+        &self,
+        term: &Term,
+    ) -> Result<Term, String> {
+        let mut out = Term {
+            names: Vec::new(),
+            size: term.size,
+        };
+        for name in &term.names {
+            match self.solved.get(name) {
+                Some(solution) => {
+                    let solution = self.resolve(solution)?;
+                    out.names.extend(solution.names);
+                    out.size = out
+                        .size
+                        .checked_mul(solution.size)
+                        .ok_or_else(|| "size overflow".to_string())?;
+                }
+                None => out.names.push(name.clone()),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Unify two terms.
+    ///
+    /// Common names cancel; one remaining name is then solved in terms of the
+    /// other side. Anything else has no pattern representation.
+    fn unify(
+        &mut self,
+        a: &Term,
+        b: &Term,
+    ) -> Result<Unified, String> {
+        let mut a = self.resolve(a)?;
+        let mut b = self.resolve(b)?;
+        a.names
+            .retain(|name| match b.names.iter().position(|n| n == name) {
+                Some(idx) => {
+                    b.names.remove(idx);
+                    false
+                }
+                None => true,
+            });
+
+        // Prefer solving the other pattern's name; so `self`'s names are kept.
+        let (name, rest, scale) = match (a.names.as_slice(), b.names.as_slice()) {
+            ([], []) if a.size == b.size => return Ok(Unified::Ok),
+            ([], []) => return Ok(Unified::Incompatible),
+            (_, [name]) => (name.clone(), a, b.size),
+            ([name], _) => (name.clone(), b, a.size),
+            _ => {
+                return Err(format!(
+                    "cannot express {} = {} as a pattern",
+                    describe(&a),
+                    describe(&b)
+                ));
+            }
+        };
+
+        // name * scale = rest
+        let size = match rest.size.checked_rem(scale) {
+            Some(0) => rest.size / scale,
+            Some(_) if rest.names.is_empty() => return Ok(Unified::Incompatible),
+            _ => {
+                return Err(format!(
+                    "cannot express {name} * {scale} = {} as a pattern",
+                    describe(&rest)
+                ));
+            }
+        };
+        let solution = Term {
+            names: rest.names,
+            size,
+        };
+        if solution.component(false).is_none() {
+            return Err(format!(
+                "cannot express {name} = {} as a pattern",
+                describe(&solution)
+            ));
+        }
+        self.solved.insert(name, solution);
+        Ok(Unified::Ok)
+    }
+
+    /// Apply the substitution to a component.
+    fn apply(
+        &self,
+        component: &PatternComponent,
+    ) -> Result<PatternComponent, String> {
+        if let PatternComponent::Ellipsis = component {
+            return Ok(PatternComponent::Ellipsis);
+        }
+        let term = self.resolve(&Term::of(component))?;
+        term.component(matches!(component, PatternComponent::Composite(_)))
+            .ok_or_else(|| format!("cannot express {} as a pattern", describe(&term)))
+    }
+}
+
+/// Describe a term, e.g. `(h k)` or `(h k) * 2`.
+fn describe(term: &Term) -> String {
+    match (term.names.as_slice(), term.size) {
+        ([], size) => size.to_string(),
+        (names, 1) => format!("({})", names.join(" ")),
+        (names, size) => format!("({}) * {size}", names.join(" ")),
+    }
+}
+
+impl ShapePattern {
+    /// Concatenate two patterns.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if both patterns have an ellipsis.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use burn_contracts::shapes::ShapePattern;
+    ///
+    /// let batch = ShapePattern::parse("b ...").unwrap();
+    /// let image = ShapePattern::parse("c h w").unwrap();
+    /// assert_eq!(batch.concat(&image).unwrap().to_string(), "b ... c h w");
+    /// ```
+    pub fn concat(
+        &self,
+        other: &ShapePattern,
+    ) -> Result<ShapePattern, ShapePatternError> {
+        ShapePattern::new(
+            self.components()
+                .iter()
+                .chain(other.components())
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Replace each occurrence of the dimension `name` with a replacement pattern.
+    ///
+    /// A top-level `name` is replaced by all of the replacement's components.
+    /// Inside a composite, the replacement must be a single dimension or composite,
+    /// whose factors are spliced into the composite.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if `replacement` cannot be parsed, cannot be placed inside
+    /// a composite, or the result has more than one ellipsis.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use burn_contracts::shapes::ShapePattern;
+    ///
+    /// let pattern = ShapePattern::parse("b h w (h c)").unwrap();
+    /// assert_eq!(
+    ///     pattern.substitute("h", "(h p)").unwrap().to_string(),
+    ///     "b (h p) w (h p c)"
+    /// );
+    /// ```
+    pub fn substitute(
+        &self,
+        name: &str,
+        replacement: &str,
+    ) -> Result<ShapePattern, ShapePatternError> {
+        self.substitute_pattern(name, &ShapePattern::parse(replacement)?)
+    }
+
+    /// Replace each occurrence of the dimension `name` with a replacement pattern.
+    ///
+    /// See `substitute()`.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if `replacement` cannot be placed inside a composite,
+    /// or the result has more than one ellipsis.
+    pub fn substitute_pattern(
+        &self,
+        name: &str,
+        replacement: &ShapePattern,
+    ) -> Result<ShapePattern, ShapePatternError> {
+        let mut components = Vec::new();
+        for component in self.components() {
+            match component {
+                PatternComponent::Dim(id) if id == name => {
+                    components.extend(replacement.components().iter().cloned());
+                }
+                PatternComponent::Composite(ids) if ids.iter().any(|id| id == name) => {
+                    let inner = match replacement.components() {
+                        [PatternComponent::Dim(id)] => std::slice::from_ref(id),
+                        [PatternComponent::Composite(ids)] => ids.as_slice(),
+                        _ => {
+                            return Err(ShapePatternError::InvalidPattern {
+                                pattern: self.to_string(),
+                                message: format!(
+                                    "Cannot substitute \"{replacement}\" for \"{name}\" inside composite {component}"
+                                ),
+                            });
+                        }
+                    };
+                    let mut spliced = Vec::new();
+                    for id in ids {
+                        if id == name {
+                            spliced.extend(inner.iter().cloned());
+                        } else {
+                            spliced.push(id.clone());
+                        }
+                    }
+                    components.push(PatternComponent::Composite(spliced));
+                }
+                component => components.push(component.clone()),
+            }
+        }
+        ShapePattern::new(components)
+    }
+
+    /// Unify two patterns; finding the most general pattern matched by shapes which match both.
+    ///
+    /// Names are shared between the patterns; a name may be solved as another name,
+    /// or as a product of names (`d` = `(h k)`); where there is a choice, the names of
+    /// `self` are kept.
+    ///
+    /// When both patterns have an ellipsis, the unifier is the one in which the
+    /// ellipses overlap; shapes on which one pattern's prefix overlaps the other's
+    /// suffix are not covered.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the patterns might both match some shape, but the constraints
+    /// between them cannot be expressed as a pattern; e.g. `(a b)` = `(c d)`.
+    ///
+    /// ## Returns
+    ///
+    /// Returns `None` if no shape can match both patterns; because their ranks differ,
+    /// or their fixed sizes conflict.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use burn_contracts::shapes::{PatternComponent, ShapePattern};
+    ///
+    /// let a = ShapePattern::parse("b ... d").unwrap();
+    /// let b = ShapePattern::parse("b t (h k)").unwrap();
+    ///
+    /// let unifier = a.unify(&b).unwrap().unwrap();
+    /// assert_eq!(unifier.pattern.to_string(), "b t (h k)");
+    /// assert_eq!(
+    ///     unifier.substitution["d"],
+    ///     PatternComponent::Composite(vec!["h".to_string(), "k".to_string()])
+    /// );
+    ///
+    /// assert!(a.unify(&ShapePattern::parse("x").unwrap()).unwrap().is_none());
+    /// ```
+    pub fn unify(
+        &self,
+        other: &ShapePattern,
+    ) -> Result<Option<Unifier>, ShapePatternError> {
+        let Some(slots) = align(self, other) else {
+            return Ok(None);
+        };
+
+        let error = |message: String| ShapePatternError::InvalidPattern {
+            pattern: format!("{self} ~ {other}"),
+            message,
+        };
+
+        let mut substitution = Substitution::default();
+        for slot in &slots {
+            if let Slot::Pair(a, b) = slot {
+                let unified = substitution
+                    .unify(&Term::of(a), &Term::of(b))
+                    .map_err(|message| error(format!("Cannot unify {a} with {b}: {message}")))?;
+                if let Unified::Incompatible = unified {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let components = slots
+            .iter()
+            .map(|slot| match slot {
+                Slot::Pair(a, _) | Slot::Single(a) => substitution.apply(a),
+                Slot::Ellipsis => Ok(PatternComponent::Ellipsis),
+            })
+            .collect::<Result<_, _>>()
+            .map_err(error)?;
+
+        let solved = substitution
+            .solved
+            .keys()
+            .map(|name| {
+                let component = substitution.apply(&PatternComponent::Dim(name.clone()))?;
+                Ok((name.clone(), component))
+            })
+            .collect::<Result<_, _>>()
+            .map_err(error)?;
+
+        Ok(Some(Unifier {
+            pattern: ShapePattern::new(components)?,
+            substitution: solved,
+        }))
+    }
+}
+
+/// Align the components of two patterns; `None` if their ranks are incompatible.
+fn align<'a>(
+    a: &'a ShapePattern,
+    b: &'a ShapePattern,
+) -> Option<Vec<Slot<'a>>> {
+    let (a_prefix, a_suffix, a_ellipsis) = split(a);
+    let (b_prefix, b_suffix, b_ellipsis) = split(b);
+
+    let pair = |x: &'a [PatternComponent], y: &'a [PatternComponent]| {
+        x.iter()
+            .zip(y)
+            .map(|(x, y)| Slot::Pair(x, y))
+            .collect::<Vec<_>>()
+    };
+
+    Some(match (a_ellipsis, b_ellipsis) {
+        (false, false) => {
+            if a_prefix.len() != b_prefix.len() {
+                return None;
+            }
+            pair(a_prefix, b_prefix)
+        }
+        (true, false) | (false, true) => {
+            // One pattern is fixed-rank; the other's ellipsis absorbs its middle.
+            let (fixed, prefix, suffix, fixed_first) = if a_ellipsis {
+                (b_prefix, a_prefix, a_suffix, false)
+            } else {
+                (a_prefix, b_prefix, b_suffix, true)
+            };
+            if fixed.len() < prefix.len() + suffix.len() {
+                return None;
+            }
+            let middle = &fixed[prefix.len()..fixed.len() - suffix.len()];
+            let fixed_suffix = &fixed[fixed.len() - suffix.len()..];
+            let ordered = |x, y| if fixed_first { pair(x, y) } else { pair(y, x) };
+
+            let mut slots = ordered(&fixed[..prefix.len()], prefix);
+            slots.extend(middle.iter().map(Slot::Single));
+            slots.extend(ordered(fixed_suffix, suffix));
+            slots
+        }
+        (true, true) => {
+            let mut slots = pair(a_prefix, b_prefix);
+            let longer = if a_prefix.len() > b_prefix.len() {
+                &a_prefix[b_prefix.len()..]
+            } else {
+                &b_prefix[a_prefix.len()..]
+            };
+            slots.extend(longer.iter().map(Slot::Single));
+
+            slots.push(Slot::Ellipsis);
+
+            let n = a_suffix.len().min(b_suffix.len());
+            let longer = if a_suffix.len() > b_suffix.len() {
+                &a_suffix[..a_suffix.len() - n]
+            } else {
+                &b_suffix[..b_suffix.len() - n]
+            };
+            slots.extend(longer.iter().map(Slot::Single));
+            slots.extend(pair(
+                &a_suffix[a_suffix.len() - n..],
+                &b_suffix[b_suffix.len() - n..],
+            ));
+            slots
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> ShapePattern {
+        ShapePattern::parse(input).unwrap()
+    }
+
+    fn unified(
+        a: &str,
+        b: &str,
+    ) -> Option<String> {
+        parse(a)
+            .unify(&parse(b))
+            .unwrap()
+            .map(|u| u.pattern.to_string())
+    }
+
+    #[test]
+    fn test_concat() {
+        assert_eq!(
+            parse("b").concat(&parse("(h p) c")).unwrap(),
+            parse("b (h p) c")
+        );
+        assert!(parse("b ...").concat(&parse("... c")).is_err());
+    }
+
+    #[test]
+    fn test_substitute() {
+        let pattern = parse("b ... h (w h)");
+        assert_eq!(
+            pattern.substitute("h", "(h p)").unwrap(),
+            parse("b ... (h p) (w h p)")
+        );
+        assert_eq!(
+            pattern.substitute("h", "y").unwrap(),
+            parse("b ... y (w y)")
+        );
+        assert_eq!(pattern.substitute("x", "y").unwrap(), pattern);
+        assert_eq!(parse("b h").substitute("h", "y z").unwrap(), parse("b y z"));
+
+        // Multiple components cannot be spliced into a composite.
+        assert!(pattern.substitute("h", "y z").is_err());
+        // Nor can a second ellipsis be introduced.
+        assert!(pattern.substitute("h", "...").is_err());
+    }
+
+    #[test]
+    fn test_unify_alignment() {
+        assert_eq!(unified("a b", "c d").as_deref(), Some("a b"));
+        assert_eq!(unified("a b", "c d e"), None);
+        assert_eq!(unified("a ... z", "x y").as_deref(), Some("a z"));
+        assert_eq!(unified("x y", "a ... z").as_deref(), Some("x y"));
+        assert_eq!(unified("a ... z", "p q r s").as_deref(), Some("a q r z"));
+        assert_eq!(unified("a b ... z", "x").as_deref(), None);
+        assert_eq!(
+            unified("a b ... z", "x ... y z").as_deref(),
+            Some("a b ... y z")
+        );
+    }
+
+    #[test]
+    fn test_unify_substitution() {
+        let unifier = parse("b t d").unify(&parse("b t (h k)")).unwrap().unwrap();
+        assert_eq!(unifier.pattern, parse("b t (h k)"));
+        assert_eq!(
+            unifier.substitution,
+            [(
+                "d".to_string(),
+                PatternComponent::Composite(vec!["h".to_string(), "k".to_string()])
+            )]
+            .into()
+        );
+
+        // Solved names propagate to other occurrences.
+        assert_eq!(
+            unified("x (x y)", "(a b) z").as_deref(),
+            Some("(a b) (a b y)")
+        );
+
+        // Common factors cancel.
+        assert_eq!(unified("(a p)", "(b p)").as_deref(), Some("(a p)"));
+
+        assert!(parse("(a b)").unify(&parse("(c d)")).is_err());
+
+        // Literals solve names, and conflicting literals cannot unify.
+        let unifier = parse("b d").unify(&parse("b 3")).unwrap().unwrap();
+        assert_eq!(unifier.pattern, parse("b 3"));
+        assert_eq!(unifier.substitution["d"], PatternComponent::Literal(3));
+        assert_eq!(unified("x x", "3 y").as_deref(), Some("3 3"));
+        assert_eq!(unified("b 3", "b 4"), None);
+        assert_eq!(unified("x x", "3 4"), None);
+
+        // A scaled name has no pattern representation.
+        assert!(parse("x (x y)").unify(&parse("3 z")).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use crate::shapes::exp::{PatternComponent, ShapePattern, ShapePatternError};
+use crate::shapes::parser::{convert, parse_syntax};
+use crate::shapes::suggest::closest_name;
+use burn_contracts_grammar::Component;
+use std::collections::BTreeMap;
+
+/// A table of named sub-patterns, referenced in patterns as `$name`.
+///
+/// Aliases may reference other aliases; they are expanded recursively when a
+/// pattern is parsed, so definition order does not matter. Aliases are only
+/// allowed at the top level of a pattern, not inside composites.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shapes::PatternAliases;
+///
+/// let mut aliases = PatternAliases::new();
+/// aliases.define("img", "c h w").unwrap();
+/// aliases.define("batch", "b $img").unwrap();
+///
+/// let pattern = aliases.parse("$batch").unwrap();
+/// assert_eq!(pattern.to_string(), "b c h w");
+/// assert_eq!(format!("{pattern:#}"), "$batch");
+///
+/// let [h] = pattern
+///     .match_bindings(&[2, 3, 32, 32], &[("c", 3)])
+///     .unwrap()
+///     .select(["h"]);
+/// assert_eq!(h, 32);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatternAliases {
+    aliases: BTreeMap<String, Vec<Component>>,
+}
+
+impl PatternAliases {
+    /// Create an empty alias table.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define, or redefine, an alias.
+    ///
+    /// Only the syntax of `source` is checked here; references to other aliases,
+    /// and cycles, are checked when the alias is used.
+    ///
+    /// ## Parameters
+    ///
+    /// - `name`: The alias name, without the `$`.
+    /// - `source`: The pattern the alias expands to.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if `source` cannot be parsed.
+    pub fn define(
+        &mut self,
+        name: impl Into<String>,
+        source: &str,
+    ) -> Result<(), ShapePatternError> {
+        self.aliases.insert(name.into(), parse_syntax(source)?);
+        Ok(())
+    }
+
+    /// Check if an alias is defined.
+    #[must_use]
+    pub fn contains(
+        &self,
+        name: &str,
+    ) -> bool {
+        self.aliases.contains_key(name)
+    }
+
+    /// Iterate over the alias names, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.aliases.keys().map(String::as_str)
+    }
+
+    /// Parse a `ShapePattern`, expanding any `$alias` references.
+    ///
+    /// The pattern remembers its top-level aliases; see the `ShapePattern` `Display` docs.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the input string cannot be parsed,
+    /// references an unknown alias, the aliases form a cycle,
+    /// or the expanded pattern is invalid.
+    pub fn parse(
+        &self,
+        input: &str,
+    ) -> Result<ShapePattern, ShapePatternError> {
+        let mut components = Vec::new();
+        let mut spans = Vec::new();
+        for component in parse_syntax(input)? {
+            match component {
+                Component::Alias(name) => {
+                    let start = components.len();
+                    self.expand(input, &name, &mut Vec::new(), &mut components)?;
+                    spans.push((name, start..components.len()));
+                }
+                component => components.extend(convert(component)),
+            }
+        }
+        match ShapePattern::new(components) {
+            Ok(pattern) => Ok(pattern.with_aliases(spans)),
+            Err(ShapePatternError::InvalidPattern { message, .. }) => {
+                Err(ShapePatternError::InvalidPattern {
+                    pattern: input.to_string(),
+                    message,
+                })
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Expand `name` into `out`; `stack` holds the aliases being expanded, for cycle detection.
+    fn expand<'a>(
+        &'a self,
+        input: &str,
+        name: &'a str,
+        stack: &mut Vec<&'a str>,
+        out: &mut Vec<PatternComponent>,
+    ) -> Result<(), ShapePatternError> {
+        let invalid = |message: String| ShapePatternError::InvalidPattern {
+            pattern: input.to_string(),
+            message,
+        };
+
+        if stack.contains(&name) {
+            let cycle: Vec<String> = stack
+                .iter()
+                .skip_while(|n| **n != name)
+                .chain([&name])
+                .map(|n| format!("${n}"))
+                .collect();
+            return Err(invalid(format!("Alias cycle: {}", cycle.join(" -> "))));
+        }
+
+        let Some(body) = self.aliases.get(name) else {
+            let suggestion = closest_name(name, self.names())
+                .map(|s| format!(" (did you mean \"${s}\"?)"))
+                .unwrap_or_default();
+            return Err(invalid(format!("Unknown alias \"${name}\"{suggestion}")));
+        };
+
+        stack.push(name);
+        for component in body {
+            match component {
+                Component::Alias(inner) => self.expand(input, inner, stack, out)?,
+                component => out.extend(convert(component.clone())),
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(defs: &[(&str, &str)]) -> PatternAliases {
+        let mut aliases = PatternAliases::new();
+        for (name, source) in defs {
+            aliases.define(*name, source).unwrap();
+        }
+        aliases
+    }
+
+    #[test]
+    fn test_expansion() {
+        let aliases = aliases(&[
+            ("img", "c h w"),
+            ("tokens", "b t d"),
+            ("nested", "$img ..."),
+        ]);
+        assert!(aliases.contains("img"));
+
+        let pattern = aliases.parse("b $img").unwrap();
+        assert_eq!(pattern, ShapePattern::parse("b c h w").unwrap());
+        assert_eq!(pattern.to_string(), "b c h w");
+        assert_eq!(format!("{pattern:#}"), "b $img");
+
+        let pattern = aliases.parse("$tokens (x y) $nested").unwrap();
+        assert_eq!(pattern.to_string(), "b t d (x y) c h w ...");
+        assert_eq!(format!("{pattern:#}"), "$tokens (x y) $nested");
+
+        // Without aliases, the compact form is the canonical form.
+        let pattern = aliases.parse("b (h p)").unwrap();
+        assert_eq!(format!("{pattern:#}"), "b (h p)");
+    }
+
+    #[test]
+    fn test_errors() {
+        let aliases = aliases(&[
+            ("img", "c h w"),
+            ("a", "x $b"),
+            ("b", "$c y"),
+            ("c", "$a"),
+            ("many", "... x"),
+        ]);
+
+        assert_eq!(
+            aliases.parse("b $imgs").unwrap_err().to_string(),
+            "Invalid pattern \"b $imgs\": Unknown alias \"$imgs\" (did you mean \"$img\"?)"
+        );
+        assert_eq!(
+            aliases.parse("z $b").unwrap_err().to_string(),
+            "Invalid pattern \"z $b\": Alias cycle: $b -> $c -> $a -> $b"
+        );
+        assert_eq!(
+            aliases.parse("$many ...").unwrap_err().to_string(),
+            "Invalid pattern \"$many ...\": Only one ellipsis is allowed"
+        );
+        assert!(matches!(
+            aliases.parse("b $"),
+            Err(ShapePatternError::ParseError { .. })
+        ));
+
+        let mut aliases = PatternAliases::new();
+        assert!(aliases.define("bad", "b (").is_err());
+    }
+}
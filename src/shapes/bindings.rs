@@ -1,7 +1,52 @@
-use std::collections::HashMap;
+use crate::shapes::ShapeMatch;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::iter;
 use std::slice;
 
+/// Derive `ShapeBindingSource` for `&Struct`, exporting its named `usize` fields.
+///
+/// Fields can be renamed with `#[shape_binding(rename = "name")]`,
+/// and excluded with `#[shape_binding(skip)]`.
+///
+/// ```rust
+/// use burn_contracts::shapes::ShapePattern;
+/// use burn_contracts::shapes::bindings::ShapeBindingSource;
+///
+/// #[derive(ShapeBindingSource)]
+/// struct AttentionConfig {
+///     d_model: usize,
+///     #[shape_binding(rename = "h")]
+///     n_heads: usize,
+///     #[shape_binding(skip)]
+///     dropout: f64,
+/// }
+///
+/// let config = AttentionConfig { d_model: 512, n_heads: 8, dropout: 0.1 };
+///
+/// let [k] = ShapePattern::cached_parse("b t (h k)")
+///     .unwrap()
+///     .match_bindings(&[2, 10, 512], &config)
+///     .unwrap()
+///     .select(["k"]);
+/// assert_eq!(k, 64);
+/// ```
+///
+/// Each binding name must be unique:
+///
+/// ```compile_fail
+/// use burn_contracts::shapes::bindings::ShapeBindingSource;
+///
+/// #[derive(ShapeBindingSource)]
+/// struct Config {
+///     h: usize,
+///     // error: duplicate shape binding name "h"; also the name of field `h`
+///     #[shape_binding(rename = "h")]
+///     n_heads: usize,
+/// }
+/// ```
+pub use burn_contracts_macros::ShapeBindingSource;
+
 /// A trait for types that can be used as a read-only source of shape binding references.
 ///
 /// FIXME(crutcher): Fix reference traits.
@@ -18,6 +63,17 @@ use std::slice;
 ///  - `&[(T, usize); N] where T: AsRef<str>, const N: usize`
 ///  - `&Vec<(T, usize)> where T : AsRef<str>`
 ///  - `&HashMap<String, usize>`
+///  - `&HashMap<&str, usize>`
+///  - `&BTreeMap<String, usize>`
+///  - `&ShapeMatch`
+///  - `&S` where `S` derives `ShapeBindingSource`
+///  - `Option<T> where T: ShapeBindingSource`, passed by value
+///  - `(A, B, ...)` of sources, passed by value; earlier sources take precedence
+///  - `FnBindings<F>`, from `from_fn(|name| ...)`
+///  - `Scoped<T>`, from `scoped(prefix, source)`, passed by value
+///
+/// Pattern matching looks names up with `lookup_scoped_binding()`; so dotted names
+/// fall back to bindings in enclosing scopes.
 ///
 /// ```rust
 /// use burn_contracts::shapes::bindings::{ShapeBindingSource, collect_binding_map};
@@ -40,15 +96,47 @@ pub trait ShapeBindingSource {
 
     /// Looks up the value of a shape binding by name.
     ///
-    /// Returns `None` if the shape binding is not found.
+    /// Returns `None` if the shape binding is not found. If a name is listed more than once,
+    /// the last binding wins; as in `collect_binding_map()`.
     fn lookup_shape_binding(
         &self,
         name: &str,
     ) -> Option<usize> {
         self.for_each_shape_binding()
-            .find(|(k, _)| *k == name)
+            .filter(|(k, _)| *k == name)
+            .last()
             .map(|(_, v)| v)
     }
+
+    /// Looks up the value of a shape binding by a dotted name, falling back to
+    /// enclosing scopes: `enc.attn.d` is looked up as `enc.attn.d`, then `enc.d`, then `d`.
+    ///
+    /// This is the lookup used by pattern matching.
+    fn lookup_scoped_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        if !name.contains('.') {
+            return self.lookup_shape_binding(name);
+        }
+        scope_chain(name)
+            .iter()
+            .find_map(|name| self.lookup_shape_binding(name))
+    }
+}
+
+/// The names a dotted name is looked up as, innermost scope first;
+/// `enc.attn.d` -> `enc.attn.d`, `enc.d`, `d`.
+pub(crate) fn scope_chain(name: &str) -> Vec<Cow<'_, str>> {
+    let mut chain = vec![Cow::Borrowed(name)];
+    if let Some((mut scope, leaf)) = name.rsplit_once('.') {
+        while let Some((outer, _)) = scope.rsplit_once('.') {
+            chain.push(Cow::Owned(format!("{outer}.{leaf}")));
+            scope = outer;
+        }
+        chain.push(Cow::Borrowed(leaf));
+    }
+    chain
 }
 
 impl<T> ShapeBindingSource for &[(T, usize)]
@@ -63,6 +151,16 @@ where
     fn for_each_shape_binding(&self) -> Self::Iter<'_> {
         self.iter().map(|(k, v)| (k.as_ref(), *v))
     }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.iter()
+            .rev()
+            .find(|(k, _)| k.as_ref() == name)
+            .map(|(_, v)| *v)
+    }
 }
 
 impl<const N: usize, T> ShapeBindingSource for &[(T, usize); N]
@@ -77,6 +175,16 @@ where
     fn for_each_shape_binding(&self) -> Self::Iter<'_> {
         self.iter().map(|(k, v)| (k.as_ref(), *v))
     }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.iter()
+            .rev()
+            .find(|(k, _)| k.as_ref() == name)
+            .map(|(_, v)| *v)
+    }
 }
 
 impl<T> ShapeBindingSource for &Vec<(T, usize)>
@@ -91,6 +199,16 @@ where
     fn for_each_shape_binding(&self) -> Self::Iter<'_> {
         self.iter().map(|(k, v)| (k.as_ref(), *v))
     }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.iter()
+            .rev()
+            .find(|(k, _)| k.as_ref() == name)
+            .map(|(_, v)| *v)
+    }
 }
 
 impl<S: ::std::hash::BuildHasher> ShapeBindingSource for &HashMap<String, usize, S> {
@@ -114,6 +232,263 @@ impl<S: ::std::hash::BuildHasher> ShapeBindingSource for &HashMap<String, usize,
     }
 }
 
+impl<'s, S: ::std::hash::BuildHasher> ShapeBindingSource for &HashMap<&'s str, usize, S> {
+    type Iter<'a>
+        = iter::Map<
+        std::collections::hash_map::Iter<'a, &'s str, usize>,
+        fn((&'a &'s str, &'a usize)) -> (&'a str, usize),
+    >
+    where
+        Self: 'a;
+
+    fn for_each_shape_binding(&self) -> Self::Iter<'_> {
+        self.iter().map(|(k, v)| (*k, *v))
+    }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.get(name).copied()
+    }
+}
+
+impl ShapeBindingSource for &BTreeMap<String, usize> {
+    type Iter<'a>
+        = iter::Map<
+        std::collections::btree_map::Iter<'a, String, usize>,
+        fn((&'a String, &'a usize)) -> (&'a str, usize),
+    >
+    where
+        Self: 'a;
+
+    fn for_each_shape_binding(&self) -> Self::Iter<'_> {
+        self.iter().map(|(k, v)| (k.as_ref(), *v))
+    }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.get(name).copied()
+    }
+}
+
+impl ShapeBindingSource for &ShapeMatch {
+    type Iter<'a>
+        = iter::Map<
+        std::collections::hash_map::Iter<'a, String, usize>,
+        fn((&'a String, &'a usize)) -> (&'a str, usize),
+    >
+    where
+        Self: 'a;
+
+    fn for_each_shape_binding(&self) -> Self::Iter<'_> {
+        self.bindings.iter().map(|(k, v)| (k.as_ref(), *v))
+    }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.bindings.get(name).copied()
+    }
+}
+
+impl<T: ShapeBindingSource> ShapeBindingSource for Option<T> {
+    type Iter<'a>
+        = iter::Flatten<std::option::IntoIter<T::Iter<'a>>>
+    where
+        Self: 'a;
+
+    fn for_each_shape_binding(&self) -> Self::Iter<'_> {
+        self.as_ref()
+            .map(ShapeBindingSource::for_each_shape_binding)
+            .into_iter()
+            .flatten()
+    }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.as_ref().and_then(|s| s.lookup_shape_binding(name))
+    }
+}
+
+/// A shape binding source with its names scoped under a prefix; see `scoped()`.
+#[derive(Debug, Clone)]
+pub struct Scoped<T> {
+    prefix: String,
+    names: Vec<(String, usize)>,
+    source: T,
+}
+
+/// Scope the names of a shape binding source under a dotted prefix.
+///
+/// Lookups of `prefix.name` are looked up in `source` as `name`; so sources from
+/// several sub-configs can be layered into one environment.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shapes::ShapePattern;
+/// use burn_contracts::shapes::bindings::scoped;
+///
+/// let enc = [("d_model", 512)];
+/// let dec = [("d_model", 256)];
+/// let bindings = (scoped("enc", &enc), scoped("dec", &dec));
+///
+/// let pattern = ShapePattern::parse("b (enc.d_model dec.d_model)").unwrap();
+/// assert!(pattern.match_bindings(&[2, 512 * 256], bindings).is_ok());
+/// ```
+pub fn scoped<T: ShapeBindingSource>(
+    prefix: impl Into<String>,
+    source: T,
+) -> Scoped<T> {
+    let prefix = prefix.into();
+    let names = source
+        .for_each_shape_binding()
+        .map(|(name, value)| (format!("{prefix}.{name}"), value))
+        .collect();
+    Scoped {
+        prefix,
+        names,
+        source,
+    }
+}
+
+impl<T: ShapeBindingSource> ShapeBindingSource for Scoped<T> {
+    type Iter<'a>
+        = iter::Map<slice::Iter<'a, (String, usize)>, fn(&'a (String, usize)) -> (&'a str, usize)>
+    where
+        Self: 'a;
+
+    fn for_each_shape_binding(&self) -> Self::Iter<'_> {
+        self.names.iter().map(|(k, v)| (k.as_str(), *v))
+    }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        let name = name.strip_prefix(self.prefix.as_str())?.strip_prefix('.')?;
+        self.source.lookup_shape_binding(name)
+    }
+}
+
+/// A lookup-only shape binding source backed by a closure.
+///
+/// The closure cannot be enumerated; so `for_each_shape_binding()` is empty,
+/// and these bindings will not appear in collected maps or error messages.
+#[derive(Debug, Clone, Copy)]
+pub struct FnBindings<F>(pub F);
+
+/// Wrap a `Fn(&str) -> Option<usize>` closure as a shape binding source.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shapes::bindings::{from_fn, lookup_binding};
+///
+/// let source = from_fn(|name| name.strip_prefix("n").and_then(|n| n.parse().ok()));
+/// assert_eq!(lookup_binding(&source, "n32"), Some(32));
+/// assert_eq!(lookup_binding(&source, "x"), None);
+/// ```
+pub fn from_fn<F>(f: F) -> FnBindings<F>
+where
+    F: Fn(&str) -> Option<usize>,
+{
+    FnBindings(f)
+}
+
+impl<F> ShapeBindingSource for &FnBindings<F>
+where
+    F: Fn(&str) -> Option<usize>,
+{
+    type Iter<'a>
+        = iter::Empty<(&'a str, usize)>
+    where
+        Self: 'a;
+
+    fn for_each_shape_binding(&self) -> Self::Iter<'_> {
+        iter::empty()
+    }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        (self.0)(name)
+    }
+}
+
+/// Iterator over a tuple of shape binding sources.
+///
+/// Yields each layer in order; skipping names already bound by an earlier layer.
+#[derive(Debug)]
+pub struct TupleIter<'a, T, I> {
+    source: &'a T,
+    layer: usize,
+    iters: I,
+}
+
+macro_rules! impl_tuple_source {
+    ($($name:ident $idx:tt),+) => {
+        impl<$($name: ShapeBindingSource),+> ShapeBindingSource for ($($name,)+) {
+            type Iter<'a>
+                = TupleIter<'a, Self, ($($name::Iter<'a>,)+)>
+            where
+                Self: 'a;
+
+            fn for_each_shape_binding(&self) -> Self::Iter<'_> {
+                TupleIter {
+                    source: self,
+                    layer: 0,
+                    iters: ($(self.$idx.for_each_shape_binding(),)+),
+                }
+            }
+
+            fn lookup_shape_binding(
+                &self,
+                name: &str,
+            ) -> Option<usize> {
+                None$(.or_else(|| self.$idx.lookup_shape_binding(name)))+
+            }
+        }
+
+        impl<'a, $($name: ShapeBindingSource + 'a),+> Iterator
+            for TupleIter<'a, ($($name,)+), ($($name::Iter<'a>,)+)>
+        {
+            type Item = (&'a str, usize);
+
+            fn next(&mut self) -> Option<Self::Item> {
+                loop {
+                    let item = match self.layer {
+                        $($idx => self.iters.$idx.next(),)+
+                        _ => return None,
+                    };
+                    match item {
+                        None => self.layer += 1,
+                        Some((name, value)) => {
+                            let shadowed = false
+                                $(|| ($idx < self.layer
+                                    && self.source.$idx.lookup_shape_binding(name).is_some()))+;
+                            if !shadowed {
+                                return Some((name, value));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+}
+
+impl_tuple_source!(A 0, B 1);
+impl_tuple_source!(A 0, B 1, C 2);
+impl_tuple_source!(A 0, B 1, C 2, D 3);
+
 /// Collects the shape bindings into a `HashMap<String, usize>`.
 pub fn collect_binding_map<T: ShapeBindingSource>(bindings: T) -> HashMap<String, usize> {
     bindings
@@ -152,6 +527,23 @@ mod test {
         assert_eq!(map.get("x"), None);
     }
 
+    #[test]
+    fn test_duplicate_bindings_last_wins() {
+        let source = [("b", 2), ("c", 3), ("b", 4)];
+        assert_eq!(collect_binding_map(&source)["b"], 4);
+        assert_eq!(lookup_binding(&source, "b"), Some(4));
+        assert_eq!(lookup_binding(&source[..], "b"), Some(4));
+        assert_eq!(lookup_binding(&source.to_vec(), "b"), Some(4));
+        assert_eq!(lookup_binding(&source, "x"), None);
+
+        let [b] = crate::shapes::ShapePattern::parse("b c")
+            .unwrap()
+            .match_bindings(&[4, 3], &source)
+            .unwrap()
+            .select(["b"]);
+        assert_eq!(b, 4);
+    }
+
     #[test]
     fn test_from_str_array() {
         let source: [(&str, usize); 2] = [("a", 1), ("b", 2)];
@@ -217,4 +609,159 @@ mod test {
         assert_eq!(lookup_binding(&source, "a"), Some(1));
         assert_eq!(lookup_binding(&source, "x"), None);
     }
+
+    #[test]
+    fn test_from_str_hashmap() {
+        let mut source: HashMap<&str, usize> = Default::default();
+        source.insert("a", 1);
+        source.insert("b", 2);
+
+        let pairs = collect_sorted_binding_list(&source);
+
+        assert_eq!(pairs, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+        assert_eq!(lookup_binding(&source, "a"), Some(1));
+        assert_eq!(lookup_binding(&source, "x"), None);
+    }
+
+    #[test]
+    fn test_from_btreemap() {
+        let mut source: BTreeMap<String, usize> = Default::default();
+        source.insert("a".to_string(), 1);
+        source.insert("b".to_string(), 2);
+
+        let pairs = collect_sorted_binding_list(&source);
+
+        assert_eq!(pairs, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+        assert_eq!(lookup_binding(&source, "a"), Some(1));
+        assert_eq!(lookup_binding(&source, "x"), None);
+    }
+
+    #[test]
+    fn test_from_shape_match() {
+        let source = crate::shapes::ShapePattern::cached_parse("a b")
+            .unwrap()
+            .match_bindings(&[1, 2], &[("a", 1)])
+            .unwrap();
+
+        let pairs = collect_sorted_binding_list(&source);
+
+        assert_eq!(pairs, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+        assert_eq!(lookup_binding(&source, "b"), Some(2));
+        assert_eq!(lookup_binding(&source, "x"), None);
+    }
+
+    #[test]
+    fn test_from_option() {
+        let source = Some(&[("a", 1)]);
+        assert_eq!(lookup_binding(source, "a"), Some(1));
+        assert_eq!(
+            collect_sorted_binding_list(source),
+            vec![("a".to_string(), 1)]
+        );
+
+        let source: Option<&[(&str, usize); 1]> = None;
+        assert_eq!(lookup_binding(source, "a"), None);
+        assert_eq!(collect_sorted_binding_list(source), vec![]);
+    }
+
+    #[test]
+    fn test_from_fn() {
+        let source = from_fn(|name| (name == "a").then_some(1));
+
+        assert_eq!(lookup_binding(&source, "a"), Some(1));
+        assert_eq!(lookup_binding(&source, "x"), None);
+        assert_eq!(collect_sorted_binding_list(&source), vec![]);
+    }
+
+    #[test]
+    fn test_from_tuple() {
+        let first: [(&str, usize); 2] = [("a", 1), ("b", 2)];
+        let second: Vec<(String, usize)> = vec![("b".to_string(), 20), ("c".to_string(), 30)];
+        let third = from_fn(|name| (name == "d").then_some(40));
+
+        let source = (&first, &second, &third);
+
+        assert_eq!(lookup_binding(source, "a"), Some(1));
+        assert_eq!(lookup_binding(source, "b"), Some(2));
+        assert_eq!(lookup_binding(source, "c"), Some(30));
+        assert_eq!(lookup_binding(source, "d"), Some(40));
+        assert_eq!(lookup_binding(source, "x"), None);
+
+        // Shadowed names are only yielded once, from the first layer.
+        let layered = (&first, &second);
+        let pairs: Vec<(&str, usize)> = layered.for_each_shape_binding().collect();
+        assert_eq!(pairs, vec![("a", 1), ("b", 2), ("c", 30)]);
+
+        let map = collect_binding_map(layered);
+        assert_eq!(map.get("b"), Some(&2));
+    }
+
+    #[test]
+    fn test_derive() {
+        #[derive(ShapeBindingSource)]
+        struct Config {
+            a: usize,
+            #[shape_binding(rename = "z")]
+            b: usize,
+            #[shape_binding(skip)]
+            #[allow(dead_code)]
+            name: String,
+        }
+
+        let source = Config {
+            a: 1,
+            b: 2,
+            name: "example".to_string(),
+        };
+
+        let pairs = collect_sorted_binding_list(&source);
+
+        assert_eq!(pairs, vec![("a".to_string(), 1), ("z".to_string(), 2)]);
+
+        assert_eq!(lookup_binding(&source, "a"), Some(1));
+        assert_eq!(lookup_binding(&source, "z"), Some(2));
+        assert_eq!(lookup_binding(&source, "b"), None);
+        assert_eq!(lookup_binding(&source, "name"), None);
+    }
+
+    #[test]
+    fn test_scope_chain() {
+        assert_eq!(scope_chain("d"), ["d"]);
+        assert_eq!(scope_chain("enc.d"), ["enc.d", "d"]);
+        assert_eq!(scope_chain("enc.attn.d"), ["enc.attn.d", "enc.d", "d"]);
+    }
+
+    #[test]
+    fn test_lookup_scoped_binding() {
+        let source = [("d", 1), ("enc.d", 2), ("h", 3)];
+        assert_eq!((&source).lookup_scoped_binding("enc.d"), Some(2));
+        assert_eq!((&source).lookup_scoped_binding("enc.attn.d"), Some(2));
+        assert_eq!((&source).lookup_scoped_binding("dec.d"), Some(1));
+        assert_eq!((&source).lookup_scoped_binding("enc.h"), Some(3));
+        assert_eq!((&source).lookup_scoped_binding("enc.x"), None);
+    }
+
+    #[test]
+    fn test_scoped() {
+        let enc = [("d", 512)];
+        let dec = [("d", 256)];
+        let global = [("b", 2)];
+        let source = (scoped("enc", &enc), scoped("dec", &dec), &global);
+
+        assert_eq!(source.lookup_shape_binding("enc.d"), Some(512));
+        assert_eq!(source.lookup_shape_binding("dec.d"), Some(256));
+        assert_eq!(source.lookup_shape_binding("d"), None);
+        assert_eq!(source.lookup_scoped_binding("dec.b"), Some(2));
+        assert_eq!(
+            collect_sorted_binding_list(source),
+            vec![
+                ("b".to_string(), 2),
+                ("dec.d".to_string(), 256),
+                ("enc.d".to_string(), 512),
+            ]
+        );
+    }
 }
@@ -0,0 +1,287 @@
+//! The `ShapePattern` parse cache.
+//!
+//! `ShapePattern::cached_parse()` shares parsed patterns through a cache,
+//! which can be configured process-wide with `configure_parse_cache()`.
+use crate::shapes::exp::{ShapePattern, ShapePatternError};
+use crate::shapes::parser::parse_shape_pattern;
+use once_cell::sync::Lazy;
+use quick_cache::sync::Cache;
+use quick_cache::unsync;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// The default capacity of the parse cache.
+pub const DEFAULT_PARSE_CACHE_CAPACITY: usize = 1000;
+
+/// Configuration of the parse cache.
+///
+/// A `capacity` of `0` is equivalent to `Disabled`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseCacheConfig {
+    /// Do not cache; every call parses.
+    Disabled,
+
+    /// A single process-wide cache, shared by all threads.
+    Global { capacity: usize },
+
+    /// A separate cache for each thread; with no cross-thread contention.
+    PerThread { capacity: usize },
+}
+
+impl Default for ParseCacheConfig {
+    fn default() -> Self {
+        ParseCacheConfig::Global {
+            capacity: DEFAULT_PARSE_CACHE_CAPACITY,
+        }
+    }
+}
+
+/// Hit/miss statistics of the parse cache.
+///
+/// Counts are process-wide, across all threads; and include disabled lookups as misses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ParseCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl ParseCacheStats {
+    /// The total number of lookups.
+    #[must_use]
+    pub fn lookups(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    /// The fraction of lookups which were hits; `0.0` if there were none.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn hit_rate(&self) -> f64 {
+        match self.lookups() {
+            0 => 0.0,
+            n => self.hits as f64 / n as f64,
+        }
+    }
+}
+
+type PatternCache = Cache<String, Arc<ShapePattern>>;
+type LocalPatternCache = unsync::Cache<String, Arc<ShapePattern>>;
+
+struct CacheState {
+    config: ParseCacheConfig,
+    /// Unique to each configuration of each cache; invalidates per-thread caches.
+    generation: u64,
+    global: Option<PatternCache>,
+}
+
+/// A configurable parse cache; `GLOBAL` backs `cached_parse_shape_pattern()`.
+pub(crate) struct ParseCache {
+    state: RwLock<CacheState>,
+}
+
+static GLOBAL: Lazy<ParseCache> = Lazy::new(|| ParseCache::new(ParseCacheConfig::default()));
+
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static LOCAL: RefCell<Option<(u64, LocalPatternCache)>> = const { RefCell::new(None) };
+}
+
+fn parse_miss(input: &str) -> Result<Arc<ShapePattern>, ShapePatternError> {
+    MISSES.fetch_add(1, Ordering::Relaxed);
+    parse_shape_pattern(input).map(Arc::new)
+}
+
+impl CacheState {
+    fn new(config: ParseCacheConfig) -> Self {
+        Self {
+            config,
+            generation: GENERATION.fetch_add(1, Ordering::Relaxed),
+            global: match config {
+                ParseCacheConfig::Global { capacity } if capacity > 0 => Some(Cache::new(capacity)),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl ParseCache {
+    pub(crate) fn new(config: ParseCacheConfig) -> Self {
+        Self {
+            state: RwLock::new(CacheState::new(config)),
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, CacheState> {
+        // The state is always left consistent; so a poisoned lock is still usable.
+        self.state
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Reconfigure, discarding all cached patterns in every thread.
+    pub(crate) fn configure(
+        &self,
+        config: ParseCacheConfig,
+    ) {
+        *self
+            .state
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = CacheState::new(config);
+    }
+
+    pub(crate) fn config(&self) -> ParseCacheConfig {
+        self.read().config
+    }
+
+    pub(crate) fn parse(
+        &self,
+        input: &str,
+    ) -> Result<Arc<ShapePattern>, ShapePatternError> {
+        let state = self.read();
+        match state.config {
+            ParseCacheConfig::Global { .. } => match &state.global {
+                Some(cache) => {
+                    // Counted in the closure; so a racing insert by another thread is a hit.
+                    let mut missed = false;
+                    let pattern = cache.get_or_insert_with(input, || {
+                        missed = true;
+                        parse_miss(input)
+                    })?;
+                    if !missed {
+                        HITS.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Ok(pattern)
+                }
+                None => parse_miss(input),
+            },
+            ParseCacheConfig::PerThread { capacity } if capacity > 0 => {
+                let generation = state.generation;
+                drop(state);
+                LOCAL.with_borrow_mut(|local| {
+                    let cache = match local {
+                        Some((g, cache)) if *g == generation => cache,
+                        _ => &mut local.insert((generation, unsync::Cache::new(capacity))).1,
+                    };
+                    if let Some(pattern) = cache.get(input) {
+                        HITS.fetch_add(1, Ordering::Relaxed);
+                        return Ok(pattern.clone());
+                    }
+                    let pattern = parse_miss(input)?;
+                    cache.insert(input.to_string(), pattern.clone());
+                    Ok(pattern)
+                })
+            }
+            _ => parse_miss(input),
+        }
+    }
+}
+
+/// Reconfigure the parse cache.
+///
+/// Discards all cached patterns, in every thread; statistics are kept.
+pub fn configure_parse_cache(config: ParseCacheConfig) {
+    GLOBAL.configure(config);
+}
+
+/// Get the current parse cache configuration.
+#[must_use]
+pub fn parse_cache_config() -> ParseCacheConfig {
+    GLOBAL.config()
+}
+
+/// Get the parse cache statistics.
+#[must_use]
+pub fn parse_cache_stats() -> ParseCacheStats {
+    ParseCacheStats {
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+    }
+}
+
+/// Reset the parse cache statistics to zero.
+pub fn reset_parse_cache_stats() {
+    HITS.store(0, Ordering::Relaxed);
+    MISSES.store(0, Ordering::Relaxed);
+}
+
+/// Parse a `ShapePattern` and cache the result.
+///
+/// This function is thread-safe; the cache is configured by `configure_parse_cache()`.
+/// Parse errors are not cached.
+///
+/// ## Parameters
+///
+/// - `input`: A string representation of the `ShapePattern`
+///
+/// ## Errors
+///
+/// Returns an error if the input string cannot be parsed;
+/// or the pattern is invalid.
+pub fn cached_parse_shape_pattern(input: &str) -> Result<Arc<ShapePattern>, ShapePatternError> {
+    GLOBAL.parse(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A local cache; reconfiguring the process-wide one would race with other tests.
+    #[test]
+    fn test_parse_cache_modes() {
+        assert_eq!(parse_cache_config(), ParseCacheConfig::default());
+
+        let cache = ParseCache::new(ParseCacheConfig::default());
+        let a = cache.parse("cache_test_a b").unwrap();
+        let b = cache.parse("cache_test_a b").unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+
+        cache.configure(ParseCacheConfig::PerThread { capacity: 10 });
+        assert_eq!(cache.config(), ParseCacheConfig::PerThread { capacity: 10 });
+        let c = cache.parse("cache_test_a b").unwrap();
+        let d = cache.parse("cache_test_a b").unwrap();
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert!(Arc::ptr_eq(&c, &d));
+
+        // Other threads have their own cache.
+        let e = std::thread::scope(|s| {
+            s.spawn(|| cache.parse("cache_test_a b").unwrap())
+                .join()
+                .unwrap()
+        });
+        assert!(!Arc::ptr_eq(&c, &e));
+        assert_eq!(c, e);
+
+        // Another cache on this thread does not share the per-thread cache.
+        let other = ParseCache::new(ParseCacheConfig::PerThread { capacity: 10 });
+        assert!(!Arc::ptr_eq(&c, &other.parse("cache_test_a b").unwrap()));
+
+        cache.configure(ParseCacheConfig::Disabled);
+        let f = cache.parse("cache_test_a b").unwrap();
+        let g = cache.parse("cache_test_a b").unwrap();
+        assert!(!Arc::ptr_eq(&f, &g));
+        assert_eq!(f, g);
+
+        assert!(cache.parse("cache_test_a (").is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = ParseCacheStats { hits: 3, misses: 1 };
+        assert_eq!(stats.lookups(), 4);
+        assert!((stats.hit_rate() - 0.75).abs() < f64::EPSILON);
+
+        assert!(ParseCacheStats::default().hit_rate().abs() < f64::EPSILON);
+
+        // Counters are global and shared with concurrent tests; so only check they advance.
+        let cache = ParseCache::new(ParseCacheConfig::default());
+        let before = parse_cache_stats();
+        let _ = cache.parse("cache_test_stats x").unwrap();
+        let _ = cache.parse("cache_test_stats x").unwrap();
+        let after = parse_cache_stats();
+        assert!(after.hits > before.hits);
+        assert!(after.misses > before.misses);
+    }
+}
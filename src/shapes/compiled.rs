@@ -0,0 +1,390 @@
+use crate::shapes::bindings::{ShapeBindingSource, scope_chain};
+use crate::shapes::exp::{
+    MatchErrorKind, MatchFailure, PatternComponent, ShapePattern, ShapePatternError, check_rank,
+};
+
+/// A `PatternComponent` with names resolved to slot indices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum SlotComponent {
+    Dim(usize),
+    Ellipsis,
+    Composite(Vec<usize>),
+    Literal(usize),
+}
+
+/// A `ShapePattern` with dimension names resolved to slot indices ahead of time.
+///
+/// Matching a `CompiledPattern` writes into a fixed-size `[usize; N]` array,
+/// indexed by slot; and performs no heap allocation on success.
+///
+/// Slots are numbered in order of first appearance in the pattern.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shapes::CompiledPattern;
+///
+/// let pattern = CompiledPattern::parse("b ... (h p) (w p) c").unwrap();
+/// // Resolve slots once; outside the hot path.
+/// let [b, h, w] = pattern.slots(["b", "h", "w"]).unwrap();
+///
+/// let values: [usize; 5] = pattern
+///     .match_slots(&[2, 9, 9, 20 * 4, 10 * 4, 3], &[("p", 4)])
+///     .unwrap();
+///
+/// assert_eq!([values[b], values[h], values[w]], [2, 20, 10]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CompiledPattern {
+    pattern: ShapePattern,
+    slot_names: Vec<String>,
+    /// The names each slot is looked up as; see `lookup_scoped_binding()`.
+    scope_chains: Vec<Vec<String>>,
+    components: Vec<SlotComponent>,
+    ellipsis_pos: Option<usize>,
+}
+
+impl CompiledPattern {
+    /// Compile a `ShapePattern`.
+    #[must_use]
+    pub fn new(pattern: ShapePattern) -> Self {
+        let mut slot_names: Vec<String> = Vec::new();
+        let mut slot = |name: &String| match slot_names.iter().position(|n| n == name) {
+            Some(idx) => idx,
+            None => {
+                slot_names.push(name.clone());
+                slot_names.len() - 1
+            }
+        };
+
+        let components = pattern
+            .components()
+            .iter()
+            .map(|component| match component {
+                PatternComponent::Dim(id) => SlotComponent::Dim(slot(id)),
+                PatternComponent::Ellipsis => SlotComponent::Ellipsis,
+                PatternComponent::Literal(size) => SlotComponent::Literal(*size),
+                PatternComponent::Composite(ids) => {
+                    SlotComponent::Composite(ids.iter().map(&mut slot).collect())
+                }
+            })
+            .collect();
+
+        let scope_chains = slot_names
+            .iter()
+            .map(|name| scope_chain(name).into_iter().map(Into::into).collect())
+            .collect();
+
+        Self {
+            ellipsis_pos: pattern.ellipsis_pos(),
+            pattern,
+            slot_names,
+            scope_chains,
+            components,
+        }
+    }
+
+    /// Parse and compile a `ShapePattern`.
+    ///
+    /// ## Parameters
+    ///
+    /// - `input`: A string representation of the `ShapePattern`
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the input string cannot be parsed;
+    /// or the pattern is invalid.
+    pub fn parse(input: &str) -> Result<Self, ShapePatternError> {
+        Ok(Self::new(ShapePattern::parse(input)?))
+    }
+
+    /// Get the source `ShapePattern`.
+    #[must_use]
+    pub fn pattern(&self) -> &ShapePattern {
+        &self.pattern
+    }
+
+    /// Get the slot names, in slot order.
+    #[must_use]
+    pub fn slot_names(&self) -> &[String] {
+        &self.slot_names
+    }
+
+    /// Get the number of slots; the minimum `N` for `match_slots()`.
+    #[must_use]
+    pub fn num_slots(&self) -> usize {
+        self.slot_names.len()
+    }
+
+    /// Get the slot index of a name; if it appears in the pattern.
+    #[must_use]
+    pub fn slot(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.slot_names.iter().position(|n| n == name)
+    }
+
+    /// Resolve a list of names to slot indices.
+    ///
+    /// ## Parameters
+    ///
+    /// - `keys`: The names to resolve.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if a name does not appear in the pattern.
+    pub fn slots<const K: usize>(
+        &self,
+        keys: [&str; K],
+    ) -> Result<[usize; K], ShapePatternError> {
+        let mut result = [0; K];
+        for (i, key) in keys.iter().enumerate() {
+            result[i] = self
+                .slot(key)
+                .ok_or_else(|| ShapePatternError::InvalidPattern {
+                    pattern: self.pattern.to_string(),
+                    message: format!("No dimension named \"{key}\""),
+                })?;
+        }
+        Ok(result)
+    }
+
+    /// Match a shape against the pattern, writing each slot's value into an array.
+    ///
+    /// Slots past `num_slots()` are filled with `0`.
+    ///
+    /// ## Parameters
+    ///
+    /// - `shape`: The shape to match against.
+    /// - `bindings`: The bindings to use for matching.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if `N` is smaller than `num_slots()`,
+    /// or the shape does not match the pattern.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the slot values, indexed by slot; as with `ShapePattern::match_bindings()`,
+    /// the unbound factor of a zero-sized composite is 0.
+    pub fn match_slots<const N: usize, B: ShapeBindingSource>(
+        &self,
+        shape: &[usize],
+        bindings: B,
+    ) -> Result<[usize; N], ShapePatternError> {
+        if N < self.slot_names.len() {
+            return Err(ShapePatternError::InvalidPattern {
+                pattern: self.pattern.to_string(),
+                message: format!(
+                    "{} slots are required, but only {N} are available",
+                    self.slot_names.len()
+                ),
+            });
+        }
+
+        let mut values: [Option<usize>; N] = [None; N];
+        for (slot, chain) in self.scope_chains.iter().enumerate() {
+            values[slot] = chain
+                .iter()
+                .find_map(|name| bindings.lookup_shape_binding(name));
+        }
+
+        let fail = |failure: MatchFailure| failure.into_error(shape, &self.pattern, &bindings);
+
+        let dims = shape.len();
+        check_rank(dims, self.components.len(), self.ellipsis_pos.is_some()).map_err(fail)?;
+        let non_e_comps = self.components.len() - usize::from(self.ellipsis_pos.is_some());
+
+        let mut i = 0;
+        for (idx, component) in self.components.iter().enumerate() {
+            if let SlotComponent::Ellipsis = component {
+                i += dims - non_e_comps;
+                continue;
+            }
+
+            let dim_shape = shape[i];
+            let failure = |kind, expected, message| MatchFailure {
+                kind,
+                component: Some(idx),
+                axes: i..i + 1,
+                expected,
+                actual: Some(dim_shape),
+                message,
+            };
+
+            match component {
+                SlotComponent::Ellipsis => unreachable!(),
+                SlotComponent::Literal(size) => {
+                    if *size != dim_shape {
+                        return Err(fail(failure(
+                            MatchErrorKind::ConstraintMismatch,
+                            Some(*size),
+                            format!("Constraint Mismatch @{size}: {size} != {dim_shape}"),
+                        )));
+                    }
+                }
+                SlotComponent::Dim(slot) => match values[*slot] {
+                    Some(bound_value) => {
+                        if bound_value != dim_shape {
+                            let id = &self.slot_names[*slot];
+                            return Err(fail(failure(
+                                MatchErrorKind::ConstraintMismatch,
+                                Some(bound_value),
+                                format!("Constraint Mismatch @{id}: {bound_value} != {dim_shape}"),
+                            )));
+                        }
+                    }
+                    None => values[*slot] = Some(dim_shape),
+                },
+                SlotComponent::Composite(slots) => {
+                    let mut acc: usize = 1;
+                    let mut unbound: Option<usize> = None;
+                    for slot in slots {
+                        if let Some(value) = values[*slot] {
+                            acc = acc.checked_mul(value).ok_or_else(|| {
+                                ShapePatternError::Overflow {
+                                    pattern: self.pattern.to_string(),
+                                    shape: shape.iter().map(ToString::to_string).collect(),
+                                    component: idx,
+                                    factors: slots
+                                        .iter()
+                                        .filter_map(|s| {
+                                            Some((self.slot_names[*s].clone(), values[*s]?))
+                                        })
+                                        .collect(),
+                                }
+                            })?;
+                        } else {
+                            if unbound.is_some() {
+                                return Err(fail(failure(
+                                    MatchErrorKind::Underdetermined,
+                                    None,
+                                    "Multiple unbound factors in composite".to_string(),
+                                )));
+                            }
+                            unbound = Some(*slot);
+                        }
+                    }
+                    match unbound {
+                        Some(slot) => match dim_shape.checked_rem(acc) {
+                            Some(0) => values[slot] = Some(dim_shape / acc),
+                            // A zero product matches a zero dim, for any factor.
+                            None if dim_shape == 0 => {}
+                            _ => {
+                                let factor = &self.slot_names[slot];
+                                return Err(fail(failure(
+                                    MatchErrorKind::NonDivisibleComposite,
+                                    Some(acc),
+                                    format!(
+                                        "Composite factor \"{factor}\" * {acc} != shape {dim_shape}"
+                                    ),
+                                )));
+                            }
+                        },
+                        None => {
+                            if acc != dim_shape {
+                                let component = &self.pattern.components()[idx];
+                                return Err(fail(failure(
+                                    MatchErrorKind::ConstraintMismatch,
+                                    Some(acc),
+                                    format!(
+                                        "Constraint Mismatch @{component}: {acc} != {dim_shape}"
+                                    ),
+                                )));
+                            }
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        Ok(values.map(|v| v.unwrap_or(0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots() {
+        let pattern = CompiledPattern::parse("b ... (h p) (w p) c").unwrap();
+
+        assert_eq!(pattern.slot_names(), &["b", "h", "p", "w", "c"]);
+        assert_eq!(pattern.num_slots(), 5);
+        assert_eq!(pattern.slot("w"), Some(3));
+        assert_eq!(pattern.slot("x"), None);
+        assert_eq!(pattern.slots(["c", "b"]), Ok([4, 0]));
+        assert!(pattern.slots(["x"]).is_err());
+    }
+
+    #[test]
+    fn test_match_slots() {
+        let pattern = CompiledPattern::parse("b ... (h p) (w p) c").unwrap();
+        let shape = [2, 9, 9, 20 * 4, 10 * 4, 3];
+
+        let values: [usize; 5] = pattern.match_slots(&shape, &[("p", 4)]).unwrap();
+        assert_eq!(values, [2, 20, 4, 10, 3]);
+
+        // Extra slots are zero-filled.
+        let values: [usize; 6] = pattern.match_slots(&shape, &[("p", 4)]).unwrap();
+        assert_eq!(values, [2, 20, 4, 10, 3, 0]);
+
+        // Too few slots.
+        let result: Result<[usize; 4], _> = pattern.match_slots(&shape, &[("p", 4)]);
+        assert!(matches!(
+            result,
+            Err(ShapePatternError::InvalidPattern { .. })
+        ));
+    }
+
+    #[test]
+    fn test_match_slots_agrees_with_match_bindings() {
+        type Case<'a> = (&'a str, &'a [usize], &'a [(&'a str, usize)]);
+        let cases: &[Case] = &[
+            ("b ... (h p) (w p) c", &[2, 9, 9, 80, 40, 3], &[("p", 4)]),
+            ("b ... (h p) (w p) c", &[2, 81, 40, 3], &[("p", 4)]),
+            (
+                "b ... (h p) (w p) c",
+                &[2, 80, 40, 3],
+                &[("p", 4), ("c", 4)],
+            ),
+            ("b ... (h p) (w p) c", &[2, 80, 40, 3], &[]),
+            ("a b c", &[1, 2], &[]),
+            ("a a", &[1, 2], &[]),
+            ("a b", &[1, 2, 3], &[]),
+            ("b ...", &[2], &[]),
+            ("b (h p)", &[2, 9], &[("h", 2), ("p", 4)]),
+            ("b 3 h", &[2, 3, 4], &[]),
+            ("b 3 h", &[2, 4, 4], &[]),
+            ("(b t) d", &[0, 4], &[("b", 0)]),
+            ("(b t) d", &[0, 4], &[("t", 5)]),
+            ("(b t) t", &[0, 7], &[("b", 0)]),
+            ("(b t) d", &[3, 4], &[("b", 0)]),
+            ("(b t) d", &[6, 4], &[("b", usize::MAX), ("t", 2)]),
+            ("b enc.attn.d", &[2, 8], &[("d", 8)]),
+            ("b enc.attn.d", &[2, 8], &[("enc.d", 4), ("d", 8)]),
+            ("b (enc.h dec.h)", &[2, 12], &[("enc.h", 3), ("h", 4)]),
+        ];
+
+        for (pattern, shape, bindings) in cases {
+            let expected = ShapePattern::parse(pattern)
+                .unwrap()
+                .match_bindings(shape, *bindings);
+            let compiled = CompiledPattern::parse(pattern).unwrap();
+            let actual: Result<[usize; 8], _> = compiled.match_slots(shape, *bindings);
+
+            match (expected, actual) {
+                (Ok(expected), Ok(actual)) => {
+                    for (slot, name) in compiled.slot_names().iter().enumerate() {
+                        assert_eq!(expected.bindings[name], actual[slot]);
+                    }
+                }
+                (Err(expected), Err(actual)) => assert_eq!(expected, actual),
+                (expected, actual) => panic!("{expected:?} != {actual:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Runtime support for the `#[shape_contract]` attribute macro.
+use crate::level::ContractLevel;
+use crate::shapes::bindings::ShapeBindingSource;
+use crate::shapes::exp::ShapePattern;
+use crate::violation::{Violation, report_violation};
+use burn::prelude::{Backend, Tensor};
+use burn::tensor::BasicOps;
+use std::collections::HashMap;
+
+/// A value whose shape a `#[shape_contract]` can check.
+///
+/// Implemented for tensors, and references to them; and for `Option` and `Result`,
+/// which are checked when they hold a value, so fallible functions can be annotated.
+pub trait ContractShape {
+    /// The shape to check; `None` if there is nothing to check.
+    fn contract_shape(&self) -> Option<Vec<usize>>;
+}
+
+impl<B, const D: usize, K> ContractShape for Tensor<B, D, K>
+where
+    B: Backend,
+    K: BasicOps<B>,
+{
+    fn contract_shape(&self) -> Option<Vec<usize>> {
+        Some(self.dims().to_vec())
+    }
+}
+
+impl<T: ContractShape + ?Sized> ContractShape for &T {
+    fn contract_shape(&self) -> Option<Vec<usize>> {
+        (**self).contract_shape()
+    }
+}
+
+impl<T: ContractShape + ?Sized> ContractShape for &mut T {
+    fn contract_shape(&self) -> Option<Vec<usize>> {
+        (**self).contract_shape()
+    }
+}
+
+impl<T: ContractShape> ContractShape for Option<T> {
+    fn contract_shape(&self) -> Option<Vec<usize>> {
+        self.as_ref()?.contract_shape()
+    }
+}
+
+impl<T: ContractShape, E> ContractShape for Result<T, E> {
+    fn contract_shape(&self) -> Option<Vec<usize>> {
+        self.as_ref().ok()?.contract_shape()
+    }
+}
+
+/// Borrow a shape binding source as a source; for layering it in a tuple.
+struct SourceRef<'a, S>(&'a S);
+
+impl<S: ShapeBindingSource> ShapeBindingSource for SourceRef<'_, S> {
+    type Iter<'b>
+        = S::Iter<'b>
+    where
+        Self: 'b;
+
+    fn for_each_shape_binding(&self) -> Self::Iter<'_> {
+        self.0.for_each_shape_binding()
+    }
+
+    fn lookup_shape_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.0.lookup_shape_binding(name)
+    }
+
+    fn lookup_scoped_binding(
+        &self,
+        name: &str,
+    ) -> Option<usize> {
+        self.0.lookup_scoped_binding(name)
+    }
+}
+
+/// The binding environment of one call to a `#[shape_contract]` function.
+///
+/// Shared by the checks of the arguments and the return value;
+/// so a dimension bound by one must agree with all the others.
+/// Names not yet bound are looked up in the `bindings = ...` source of the contract.
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+pub struct ContractBindings<S = &'static [(&'static str, usize)]> {
+    function: &'static str,
+    bindings: HashMap<String, usize>,
+    source: S,
+}
+
+impl ContractBindings {
+    /// Start the checks of a call to `function`.
+    #[must_use]
+    pub fn new(function: &'static str) -> Self {
+        Self {
+            function,
+            bindings: HashMap::new(),
+            source: &[],
+        }
+    }
+
+    /// Start the checks of a call to `function`; if `ContractLevel::Shapes` is enabled.
+    #[must_use]
+    pub fn if_enabled(function: &'static str) -> Option<Self> {
+        ContractLevel::Shapes
+            .is_enabled()
+            .then(|| Self::new(function))
+    }
+
+    /// Look up names not bound by the checks in `source`;
+    /// the `bindings = ...` of the contract.
+    #[must_use]
+    pub fn with_bindings<S: ShapeBindingSource>(
+        self,
+        source: S,
+    ) -> ContractBindings<S> {
+        ContractBindings {
+            function: self.function,
+            bindings: self.bindings,
+            source,
+        }
+    }
+}
+
+impl<S: ShapeBindingSource> ContractBindings<S> {
+    /// The bindings so far.
+    #[must_use]
+    pub fn bindings(&self) -> &HashMap<String, usize> {
+        &self.bindings
+    }
+
+    /// Check `value` (the argument or return value `name`) against `pattern`,
+    /// and add the dimensions it binds to the environment.
+    ///
+    /// Reports a `Violation` if the shape does not match the pattern.
+    #[track_caller]
+    pub fn check<T: ContractShape + ?Sized>(
+        &mut self,
+        name: &str,
+        pattern: &ShapePattern,
+        value: &T,
+    ) {
+        let Some(shape) = value.contract_shape() else {
+            return;
+        };
+        match pattern.match_bindings(&shape, (&self.bindings, SourceRef(&self.source))) {
+            Ok(m) => self.bindings.extend(m.bindings),
+            Err(err) => report_violation(&Violation::new(
+                "shape_contract",
+                ContractLevel::Shapes,
+                format!(
+                    "Shape contract of `{}` violated by `{name}`: {err}",
+                    self.function
+                ),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::bindings::{from_fn, scoped};
+    use burn::backend::NdArray;
+
+    #[test]
+    fn test_contract_bindings() {
+        let device = Default::default();
+        let x = Tensor::<NdArray, 3>::zeros([2, 3, 4], &device);
+        let y = Tensor::<NdArray, 2>::zeros([2, 12], &device);
+
+        let mut env = ContractBindings::new("f").with_bindings(&[("c", 3)]);
+        env.check("x", &ShapePattern::parse("b c w").unwrap(), &x);
+        env.check("y", &ShapePattern::parse("b (c w)").unwrap(), &Some(&y));
+        env.check(
+            "z",
+            &ShapePattern::parse("q").unwrap(),
+            &None::<&Tensor<NdArray, 2>>,
+        );
+        assert_eq!(env.bindings().len(), 3);
+        assert_eq!(env.bindings()["w"], 4);
+    }
+
+    #[test]
+    fn test_contract_lookup_only_bindings() {
+        let device = Default::default();
+        let x = Tensor::<NdArray, 3>::zeros([2, 6, 512], &device);
+
+        let patches = from_fn(|name| (name == "p").then_some(2));
+        let enc = [("d_model", 512)];
+        let mut env = ContractBindings::new("f").with_bindings((&patches, scoped("enc", &enc)));
+        env.check(
+            "x",
+            &ShapePattern::parse("b (h p) enc.d_model").unwrap(),
+            &x,
+        );
+        assert_eq!(env.bindings()["h"], 3);
+        assert_eq!(env.bindings()["p"], 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Shape contract of `f` violated by `return`")]
+    fn test_contract_violation() {
+        let device = Default::default();
+        let x = Tensor::<NdArray, 2>::zeros([2, 3], &device);
+
+        let mut env = ContractBindings::new("f");
+        env.check("x", &ShapePattern::parse("b c").unwrap(), &x);
+        env.check("return", &ShapePattern::parse("c b").unwrap(), &x);
+    }
+}
@@ -0,0 +1,340 @@
+//! `miette::Diagnostic` support for `ShapePatternError`; enabled by the `miette` feature.
+//!
+//! The pattern string is the diagnostic source code; labels point at the
+//! offending span, component, or dimension names within it.
+use crate::shapes::exp::{MatchErrorKind, ShapePatternError};
+use burn_contracts_grammar::describe_expected;
+use miette::{Diagnostic, LabeledSpan, SourceCode};
+use std::fmt::Display;
+use std::ops::Range;
+
+/// The byte ranges of the top-level components of a pattern string.
+fn component_spans(pattern: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut chars = pattern.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        if c == '(' {
+            for (i, c) in chars.by_ref() {
+                end = i + c.len_utf8();
+                if c == ')' {
+                    break;
+                }
+            }
+        } else {
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_whitespace() || c == '(' {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+        }
+        spans.push(start..end);
+    }
+    spans
+}
+
+/// The byte range of the first occurrence of the dimension `name` in a pattern string.
+fn name_span(
+    pattern: &str,
+    name: &str,
+) -> Option<Range<usize>> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    let mut start = None;
+    for (i, c) in pattern.char_indices().chain([(pattern.len(), ' ')]) {
+        match (start, is_ident(c)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                if &pattern[s..i] == name {
+                    return Some(s..i);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Diagnostic for ShapePatternError {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let code = match self {
+            ShapePatternError::ParseError { .. } => "burn_contracts::parse_error",
+            ShapePatternError::InvalidPattern { .. } => "burn_contracts::invalid_pattern",
+            ShapePatternError::UnusedBindings { .. } => "burn_contracts::unused_bindings",
+            ShapePatternError::UnboundDimensions { .. } => "burn_contracts::unbound_dimensions",
+            ShapePatternError::MatchError { details, .. }
+            | ShapePatternError::SymbolicMatchError { details, .. } => match details.kind {
+                MatchErrorKind::Rank => "burn_contracts::match::rank",
+                MatchErrorKind::ConstraintMismatch => "burn_contracts::match::constraint_mismatch",
+                MatchErrorKind::NonDivisibleComposite => {
+                    "burn_contracts::match::non_divisible_composite"
+                }
+                MatchErrorKind::Underdetermined => "burn_contracts::match::underdetermined",
+            },
+            ShapePatternError::Overflow { .. } => "burn_contracts::match::overflow",
+        };
+        Some(Box::new(code))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let help = match self {
+            ShapePatternError::ParseError { .. } => {
+                "patterns are whitespace-separated names, sizes, '(...)' composites, and at most one '...'"
+                    .to_string()
+            }
+            ShapePatternError::InvalidPattern { .. } => return None,
+            ShapePatternError::UnusedBindings { unused, .. } => {
+                let suggestions: Vec<String> = unused
+                    .iter()
+                    .filter_map(|u| {
+                        u.suggestion
+                            .as_ref()
+                            .map(|s| format!("rename \"{}\" to \"{s}\"", u.name))
+                    })
+                    .collect();
+                if suggestions.is_empty() {
+                    "remove bindings which the pattern does not reference".to_string()
+                } else {
+                    suggestions.join("; ")
+                }
+            }
+            ShapePatternError::UnboundDimensions { names, .. } => {
+                format!("bind {names:?} before matching, or disable `require_bound`")
+            }
+            ShapePatternError::MatchError { pattern, details, .. }
+            | ShapePatternError::SymbolicMatchError { pattern, details, .. } => match details.kind {
+                MatchErrorKind::Rank => format!(
+                    "the pattern requires {} {} dimensions; the shape has {}",
+                    if pattern.contains("...") {
+                        "at least"
+                    } else {
+                        "exactly"
+                    },
+                    details.expected.unwrap_or(0),
+                    details.axes.end,
+                ),
+                MatchErrorKind::ConstraintMismatch => format!(
+                    "axis {} is {}, but the bindings require {}",
+                    details.axes.start,
+                    details.actual.unwrap_or(0),
+                    details.expected.unwrap_or(0),
+                ),
+                MatchErrorKind::NonDivisibleComposite => format!(
+                    "axis {} is {}, which is not divisible by the bound factors ({})",
+                    details.axes.start,
+                    details.actual.unwrap_or(0),
+                    details.expected.unwrap_or(0),
+                ),
+                MatchErrorKind::Underdetermined => {
+                    "bind all but one factor of the composite".to_string()
+                }
+            },
+            ShapePatternError::Overflow { .. } => {
+                "the bound factors multiply past usize::MAX; check the bindings".to_string()
+            }
+        };
+        Some(Box::new(help))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        match self {
+            ShapePatternError::ParseError { pattern, .. }
+            | ShapePatternError::InvalidPattern { pattern, .. }
+            | ShapePatternError::UnusedBindings { pattern, .. }
+            | ShapePatternError::UnboundDimensions { pattern, .. }
+            | ShapePatternError::MatchError { pattern, .. }
+            | ShapePatternError::SymbolicMatchError { pattern, .. }
+            | ShapePatternError::Overflow { pattern, .. } => Some(pattern),
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let labels: Vec<LabeledSpan> = match self {
+            ShapePatternError::ParseError { span, expected, .. } => {
+                vec![LabeledSpan::new_with_span(
+                    Some(describe_expected(expected)),
+                    span.clone(),
+                )]
+            }
+            ShapePatternError::InvalidPattern { .. } => return None,
+            ShapePatternError::UnusedBindings { pattern, unused } => unused
+                .iter()
+                .filter_map(|u| {
+                    let suggestion = u.suggestion.as_ref()?;
+                    let span = name_span(pattern, suggestion)?;
+                    Some(LabeledSpan::new_with_span(
+                        Some(format!("did you mean this, instead of \"{}\"?", u.name)),
+                        span,
+                    ))
+                })
+                .collect(),
+            ShapePatternError::UnboundDimensions { pattern, names } => names
+                .iter()
+                .filter_map(|name| {
+                    let span = name_span(pattern, name)?;
+                    Some(LabeledSpan::new_with_span(
+                        Some("unbound".to_string()),
+                        span,
+                    ))
+                })
+                .collect(),
+            ShapePatternError::MatchError {
+                pattern,
+                details,
+                message,
+                ..
+            }
+            | ShapePatternError::SymbolicMatchError {
+                pattern,
+                details,
+                message,
+                ..
+            } => {
+                let span = match details.component {
+                    Some(idx) => component_spans(pattern).get(idx).cloned(),
+                    None => Some(0..pattern.len()),
+                };
+                let label = format!("axes {:?}: {message}", details.axes);
+                span.map(|span| LabeledSpan::new_with_span(Some(label), span))
+                    .into_iter()
+                    .collect()
+            }
+            ShapePatternError::Overflow {
+                pattern, component, ..
+            } => component_spans(pattern)
+                .get(*component)
+                .map(|span| {
+                    LabeledSpan::new_with_span(Some("overflows usize".to_string()), span.clone())
+                })
+                .into_iter()
+                .collect(),
+        };
+        if labels.is_empty() {
+            None
+        } else {
+            Some(Box::new(labels.into_iter()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::{MatchOptions, ShapePattern};
+
+    fn labels(err: &ShapePatternError) -> Vec<(Option<String>, Range<usize>)> {
+        err.labels()
+            .into_iter()
+            .flatten()
+            .map(|l| {
+                (
+                    l.label().map(str::to_string),
+                    l.offset()..l.offset() + l.len(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spans() {
+        assert_eq!(
+            component_spans(" b ...(h p)  c"),
+            vec![1..2, 3..6, 6..11, 13..14]
+        );
+        assert_eq!(name_span("b (h p) ph p", "p"), Some(5..6));
+        assert_eq!(name_span("b (h p)", "x"), None);
+        assert_eq!(name_span("enc.d d", "d"), Some(6..7));
+    }
+
+    #[test]
+    fn test_parse_error_diagnostic() {
+        let err = ShapePattern::parse("b (h p w").unwrap_err();
+        assert_eq!(
+            err.code().unwrap().to_string(),
+            "burn_contracts::parse_error"
+        );
+        assert!(err.help().is_some());
+        assert_eq!(labels(&err), vec![(Some("expected ')'".to_string()), 8..8)]);
+    }
+
+    #[test]
+    fn test_graphical_report_shows_pattern_once() {
+        let render = |err: &ShapePatternError| {
+            let mut out = String::new();
+            miette::GraphicalReportHandler::new_themed(miette::GraphicalTheme::unicode_nocolor())
+                .render_report(&mut out, err)
+                .unwrap();
+            out
+        };
+
+        // Once in the one-line headline, and once as the labelled source code.
+        let err = ShapePattern::parse("b (hh pp w").unwrap_err();
+        let out = render(&err);
+        assert_eq!(out.matches("b (hh pp w").count(), 2, "{out}");
+        assert!(out.contains("expected ')'"), "{out}");
+
+        let err = ShapePattern::parse("b (hh pp) c")
+            .unwrap()
+            .match_bindings(&[2, 9, 3], &[("pp", 4)])
+            .unwrap_err();
+        let out = render(&err);
+        assert_eq!(out.matches("(hh pp)").count(), 2, "{out}");
+        assert!(!out.contains("b  (hh pp)  c"), "{out}");
+    }
+
+    #[test]
+    fn test_match_error_diagnostic() {
+        let err = ShapePattern::parse("b (h p) c")
+            .unwrap()
+            .match_bindings(&[2, 9, 3], &[("p", 4)])
+            .unwrap_err();
+        assert_eq!(
+            err.code().unwrap().to_string(),
+            "burn_contracts::match::non_divisible_composite"
+        );
+        assert_eq!(
+            err.help().unwrap().to_string(),
+            "axis 1 is 9, which is not divisible by the bound factors (4)"
+        );
+        assert_eq!(
+            labels(&err),
+            vec![(
+                Some("axes 1..2: Composite factor \"h\" * 4 != shape 9".to_string()),
+                2..7
+            )]
+        );
+
+        let err = ShapePattern::parse("b ... c")
+            .unwrap()
+            .match_bindings(&[2], &[("b", 2)])
+            .unwrap_err();
+        assert_eq!(
+            err.help().unwrap().to_string(),
+            "the pattern requires at least 2 dimensions; the shape has 1"
+        );
+    }
+
+    #[test]
+    fn test_strict_diagnostic() {
+        let pattern = ShapePattern::parse("batch channels").unwrap();
+        let err = pattern
+            .match_bindings_with(&[2, 3], &[("chanels", 3)], MatchOptions::STRICT)
+            .unwrap_err();
+        assert_eq!(
+            err.help().unwrap().to_string(),
+            "rename \"chanels\" to \"channels\""
+        );
+        assert_eq!(
+            labels(&err),
+            vec![(
+                Some("did you mean this, instead of \"chanels\"?".to_string()),
+                6..14
+            )]
+        );
+    }
+}
@@ -1,39 +1,392 @@
-use crate::shapes::bindings::{ShapeBindingSource, collect_binding_map, lookup_binding};
-use crate::shapes::parser::{cached_parse_shape_pattern, parse_shape_pattern};
+use crate::shapes::bindings::{ShapeBindingSource, lookup_binding, scope_chain};
+use crate::shapes::cache::cached_parse_shape_pattern;
+use crate::shapes::parser::parse_shape_pattern;
+use crate::shapes::suggest::closest_name;
+use crate::shapes::symbolic::{DimMatch, DimSize, Residual, Solved};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::ops::Range;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A parsed shape pattern.
+///
+/// `Display` shows the canonical pattern, with any aliases expanded;
+/// the alternate form (`{:#}`) shows top-level aliases in their compact `$name` form.
+///
+/// Equality and hashing consider only the expanded components.
+#[derive(Debug, Clone)]
 pub struct ShapePattern {
     ellipsis_pos: Option<usize>,
     components: Vec<PatternComponent>,
+    /// The top-level aliases this pattern was expanded from, with their component ranges.
+    aliases: Vec<(String, Range<usize>)>,
+}
+
+impl PartialEq for ShapePattern {
+    fn eq(
+        &self,
+        other: &Self,
+    ) -> bool {
+        self.components == other.components
+    }
+}
+
+impl Eq for ShapePattern {}
+
+impl std::hash::Hash for ShapePattern {
+    fn hash<H: std::hash::Hasher>(
+        &self,
+        state: &mut H,
+    ) {
+        self.components.hash(state);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PatternComponent {
     Dim(String),
     Ellipsis,
     Composite(Vec<String>),
+    /// A fixed size.
+    Literal(usize),
 }
 
 #[derive(thiserror::Error, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum ShapePatternError {
-    #[error("Parse error for \"{pattern}\"")]
-    ParseError { pattern: String },
+    /// A syntax error; `span` is the byte range of the offending input in `pattern`,
+    /// and `expected` lists the tokens which would have been accepted there.
+    ///
+    /// `render()` marks the span with a caret under the pattern.
+    #[error(
+        "Parse error for \"{pattern}\": {}",
+        burn_contracts_grammar::describe_expected(expected)
+    )]
+    ParseError {
+        pattern: String,
+        span: Range<usize>,
+        expected: Vec<String>,
+    },
 
     #[error("Invalid pattern \"{pattern}\": {message}")]
     InvalidPattern { pattern: String, message: String },
 
+    /// Strict matching found bindings which the pattern never references.
+    #[error(
+        "Unused bindings for \"{pattern}\": {}",
+        unused.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    UnusedBindings {
+        pattern: String,
+        unused: Vec<UnusedBinding>,
+    },
+
+    /// Strict matching found pattern dimensions which were not pre-bound.
+    #[error("Unbound dimensions for \"{pattern}\": {names:?}")]
+    UnboundDimensions { pattern: String, names: Vec<String> },
+
+    /// A shape which does not match a pattern.
+    ///
+    /// `render()` aligns the pattern components above the shape, with the failing component marked.
     #[error("Shape \"{shape:?}\" !~= \"{pattern}\" with {bindings:?}: {message}")]
     MatchError {
         shape: Vec<usize>,
         pattern: String,
+        /// The pattern components, rendered; for aligning the pattern above the shape.
+        #[cfg_attr(feature = "serde", serde(default))]
+        components: Box<[String]>,
         bindings: Vec<(String, usize)>,
+        /// Which component and axes failed, and how.
+        details: Box<MatchDetails>,
         message: String,
     },
+
+    /// A symbolic shape which does not match a pattern; see `ShapePattern::match_dims()`.
+    #[error("Shape \"[{}]\" !~= \"{pattern}\": {message}", shape.join(", "))]
+    SymbolicMatchError {
+        /// The shape, with each dimension formatted.
+        shape: Vec<String>,
+        pattern: String,
+        /// Which component and axes failed, and how; values are set where they are concrete.
+        details: Box<MatchDetails>,
+        message: String,
+    },
+
+    /// The product of a composite's bound factors does not fit in a `usize`.
+    #[error(
+        "Overflow matching \"{pattern}\" against [{}]: the bound factors {factors:?} of component {component} overflow usize",
+        shape.join(", ")
+    )]
+    Overflow {
+        pattern: String,
+        /// The shape, with each dimension formatted; as in `SymbolicMatchError`.
+        shape: Vec<String>,
+        /// The index of the overflowing composite component.
+        component: usize,
+        /// The bound factors with concrete values.
+        factors: Vec<(String, usize)>,
+    },
+}
+
+impl ShapePatternError {
+    /// Render the error with its context; for terminals and panic messages.
+    ///
+    /// The one-line `Display` message is followed by the pattern with a caret under
+    /// the span of a `ParseError`, or the pattern aligned above the shape of a `MatchError`.
+    /// `Display` leaves these out; so a `miette` report, which renders the pattern
+    /// as its source code, shows it once.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use burn_contracts::shapes::ShapePattern;
+    ///
+    /// let err = ShapePattern::parse("b (h p").unwrap_err();
+    /// assert_eq!(
+    ///     err.render(),
+    ///     "Parse error for \"b (h p\": expected ')'\nb (h p\n      ^"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn render(&self) -> String {
+        let context = match self {
+            ShapePatternError::ParseError { pattern, span, .. } => {
+                Some(burn_contracts_grammar::render_caret(pattern, span))
+            }
+            ShapePatternError::MatchError {
+                shape,
+                components,
+                details,
+                ..
+            } => render_alignment(components, shape, details.component),
+            _ => None,
+        };
+        match context {
+            Some(context) => format!("{self}\n{context}"),
+            None => self.to_string(),
+        }
+    }
+}
+
+/// A binding which the pattern never references; see `MatchOptions::deny_unused_bindings`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnusedBinding {
+    /// The binding name.
+    pub name: String,
+
+    /// The closest pattern dimension name, if the binding looks like a typo of it.
+    pub suggestion: Option<String>,
+}
+
+impl Display for UnusedBinding {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "\"{}\"", self.name)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean \"{suggestion}\"?)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for `ShapePattern::match_bindings_with()`.
+///
+/// The default is lenient, matching `ShapePattern::match_bindings()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct MatchOptions {
+    /// Reject bindings which the pattern never references.
+    ///
+    /// Only bindings a source enumerates are checked; `bindings::from_fn()` enumerates none.
+    pub deny_unused_bindings: bool,
+
+    /// Require every dimension name in the pattern to be pre-bound.
+    pub require_bound: bool,
+}
+
+impl MatchOptions {
+    /// Both strict checks enabled.
+    pub const STRICT: Self = Self {
+        deny_unused_bindings: true,
+        require_bound: true,
+    };
+}
+
+/// The kind of a `ShapePatternError::MatchError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatchErrorKind {
+    /// The shape has the wrong number of dimensions.
+    Rank,
+
+    /// A dimension, or a fully bound composite, differs from its bound value.
+    ConstraintMismatch,
+
+    /// A composite dimension is not divisible by the product of its bound factors.
+    NonDivisibleComposite,
+
+    /// A composite has more than one unbound factor, so cannot be solved.
+    Underdetermined,
+}
+
+/// The structured details of a `ShapePatternError::MatchError`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchDetails {
+    /// What went wrong.
+    pub kind: MatchErrorKind,
+
+    /// The index of the failing pattern component; `None` for rank errors.
+    pub component: Option<usize>,
+
+    /// The axes of the shape covered by the failing component; all axes for rank errors.
+    pub axes: Range<usize>,
+
+    /// The expected value, if known; for rank errors, the (minimum) rank.
+    pub expected: Option<usize>,
+
+    /// The actual value; for rank errors, the rank.
+    pub actual: Option<usize>,
+}
+
+/// The details of a match failure, from which a `MatchError` is built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MatchFailure {
+    pub kind: MatchErrorKind,
+    pub component: Option<usize>,
+    pub axes: Range<usize>,
+    pub expected: Option<usize>,
+    pub actual: Option<usize>,
+    pub message: String,
+}
+
+impl MatchFailure {
+    /// Build the `MatchError`; only called on failure, so it may allocate.
+    pub(crate) fn into_error<B: ShapeBindingSource>(
+        self,
+        shape: &[usize],
+        pattern: &ShapePattern,
+        bindings: &B,
+    ) -> ShapePatternError {
+        let (details, message) = self.into_parts();
+        ShapePatternError::MatchError {
+            shape: shape.to_vec(),
+            pattern: pattern.to_string(),
+            components: pattern.components.iter().map(ToString::to_string).collect(),
+            bindings: bindings
+                .for_each_shape_binding()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            details,
+            message,
+        }
+    }
+
+    /// Split into the error `details` and `message`.
+    pub(crate) fn into_parts(self) -> (Box<MatchDetails>, String) {
+        let details = Box::new(MatchDetails {
+            kind: self.kind,
+            component: self.component,
+            axes: self.axes,
+            expected: self.expected,
+            actual: self.actual,
+        });
+        (details, self.message)
+    }
+}
+
+/// Check the rank of a shape against a pattern.
+pub(crate) fn check_rank(
+    dims: usize,
+    num_components: usize,
+    has_ellipsis: bool,
+) -> Result<(), MatchFailure> {
+    let non_e_comps = num_components - usize::from(has_ellipsis);
+    let message = if non_e_comps > dims {
+        "Too few dimensions"
+    } else if !has_ellipsis && dims > non_e_comps {
+        "Too many dimensions"
+    } else {
+        return Ok(());
+    };
+    Err(MatchFailure {
+        kind: MatchErrorKind::Rank,
+        component: None,
+        axes: 0..dims,
+        expected: Some(non_e_comps),
+        actual: Some(dims),
+        message: message.to_string(),
+    })
+}
+
+/// Render rendered pattern components aligned above the dimensions of a shape;
+/// see `ShapePattern::render_alignment()`.
+///
+/// Returns `None` if the shape has the wrong rank to align.
+fn render_alignment(
+    components: &[String],
+    shape: &[usize],
+    highlight: Option<usize>,
+) -> Option<String> {
+    let dims = shape.len();
+    let ellipsis_pos = components.iter().position(|c| c == "...");
+    check_rank(dims, components.len(), ellipsis_pos.is_some()).ok()?;
+    let ellipsis_range = match ellipsis_pos {
+        Some(pos) => pos..pos + dims + 1 - components.len(),
+        None => dims..dims,
+    };
+
+    let mut i = 0;
+    let columns: Vec<(&str, String)> = components
+        .iter()
+        .enumerate()
+        .map(|(idx, component)| {
+            let axes = if Some(idx) == ellipsis_pos {
+                ellipsis_range.clone()
+            } else {
+                i..i + 1
+            };
+            i = axes.end;
+            let dims = shape[axes]
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" ");
+            (component.as_str(), dims)
+        })
+        .collect();
+
+    let mut rows = [String::new(), String::new(), String::new()];
+    for (idx, (top, bottom)) in columns.iter().enumerate() {
+        let width = top.chars().count().max(bottom.chars().count());
+        if idx > 0 {
+            for row in &mut rows {
+                row.push_str("  ");
+            }
+        }
+        rows[0].push_str(&format!("{top:width$}"));
+        rows[1].push_str(&format!("{bottom:width$}"));
+        let marker = if highlight == Some(idx) { "^" } else { " " };
+        rows[2].push_str(&marker.repeat(width));
+    }
+
+    let rows: Vec<&str> = rows
+        .iter()
+        .map(|row| row.trim_end())
+        .filter(|row| !row.is_empty())
+        .collect();
+    Some(rows.join("\n"))
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ShapeMatch {
     pub shape: Vec<usize>,
     pub bindings: HashMap<String, usize>,
@@ -72,11 +425,22 @@ impl Display for ShapePattern {
         &self,
         f: &mut std::fmt::Formatter<'_>,
     ) -> std::fmt::Result {
-        for (idx, comp) in self.components.iter().enumerate() {
+        let compact = f.alternate();
+        let mut idx = 0;
+        while idx < self.components.len() {
             if idx > 0 {
                 write!(f, " ")?;
             }
-            write!(f, "{comp}")?;
+            match self.aliases.iter().find(|(_, range)| range.start == idx) {
+                Some((name, range)) if compact => {
+                    write!(f, "${name}")?;
+                    idx = range.end;
+                }
+                _ => {
+                    write!(f, "{}", self.components[idx])?;
+                    idx += 1;
+                }
+            }
         }
         Ok(())
     }
@@ -89,6 +453,7 @@ impl Display for PatternComponent {
     ) -> std::fmt::Result {
         match self {
             PatternComponent::Dim(id) => write!(f, "{id}"),
+            PatternComponent::Literal(size) => write!(f, "{size}"),
             PatternComponent::Ellipsis => write!(f, "..."),
             PatternComponent::Composite(ids) => {
                 write!(f, "(")?;
@@ -137,9 +502,23 @@ impl ShapePattern {
         Ok(Self {
             ellipsis_pos: check_ellipsis_pos(components.as_slice())?,
             components,
+            aliases: Vec::new(),
         })
     }
 
+    /// Create a `ShapePattern` from components already validated by the `pattern!` macro.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn from_validated_components(components: Vec<PatternComponent>) -> Self {
+        Self {
+            ellipsis_pos: components
+                .iter()
+                .position(|c| matches!(c, PatternComponent::Ellipsis)),
+            components,
+            aliases: Vec::new(),
+        }
+    }
+
     /// Parse a `ShapePattern` from a string
     ///
     /// ## Parameters
@@ -156,6 +535,9 @@ impl ShapePattern {
 
     /// Parse a `ShapePattern` from a string, using a cache
     ///
+    /// The pattern is shared with the cache, rather than cloned;
+    /// see `shapes::cache` for cache configuration and statistics.
+    ///
     /// ## Parameters
     ///
     /// - `input`: A string representation of the `ShapePattern`
@@ -164,10 +546,19 @@ impl ShapePattern {
     ///
     /// Returns an error if the input string cannot be parsed;
     /// or the pattern is invalid.
-    pub fn cached_parse(input: &str) -> Result<Self, ShapePatternError> {
+    pub fn cached_parse(input: &str) -> Result<Arc<Self>, ShapePatternError> {
         cached_parse_shape_pattern(input)
     }
 
+    /// Record the top-level aliases this pattern was expanded from.
+    pub(crate) fn with_aliases(
+        mut self,
+        aliases: Vec<(String, Range<usize>)>,
+    ) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
     /// Get the components of the `ShapePattern`.
     #[must_use]
     pub fn components(&self) -> &[PatternComponent] {
@@ -177,9 +568,26 @@ impl ShapePattern {
     /// Get the position of the ellipsis in the `ShapePattern`; if any.
     #[must_use]
     pub fn ellipsis_pos(&self) -> Option<usize> {
-        self.components
-            .iter()
-            .position(|c| matches!(c, PatternComponent::Ellipsis))
+        self.ellipsis_pos
+    }
+
+    /// Get the distinct dimension names in the `ShapePattern`, in order of first appearance.
+    #[must_use]
+    pub fn dim_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for component in &self.components {
+            let ids = match component {
+                PatternComponent::Dim(id) => std::slice::from_ref(id),
+                PatternComponent::Ellipsis | PatternComponent::Literal(_) => &[],
+                PatternComponent::Composite(ids) => ids.as_slice(),
+            };
+            for id in ids {
+                if !names.contains(&id.as_str()) {
+                    names.push(id);
+                }
+            }
+        }
+        names
     }
 
     /// Check if the `ShapePattern` has an ellipsis.
@@ -188,8 +596,41 @@ impl ShapePattern {
         self.ellipsis_pos().is_some()
     }
 
+    /// Render the pattern components aligned above the dimensions of a shape.
+    ///
+    /// ```text
+    /// b  ...  (h p)  (w p)  c
+    /// 2  9 9  80     40     3
+    ///                ^^^^^
+    /// ```
+    ///
+    /// ## Parameters
+    ///
+    /// - `shape`: The shape to align against.
+    /// - `highlight`: The index of a component to mark, if any.
+    ///
+    /// ## Returns
+    ///
+    /// Returns `None` if the shape has the wrong rank to align.
+    #[must_use]
+    pub fn render_alignment(
+        &self,
+        shape: &[usize],
+        highlight: Option<usize>,
+    ) -> Option<String> {
+        let components: Vec<String> = self.components.iter().map(ToString::to_string).collect();
+        render_alignment(&components, shape, highlight)
+    }
+
     /// Assert that the `ShapeEx` matches a given shape.
     ///
+    /// Bindings the pattern never references are ignored;
+    /// see `match_bindings_with()` for strict matching.
+    ///
+    /// Zero-sized dimensions are allowed: a composite whose bound factors multiply
+    /// to 0 matches a 0 dimension, for any value of its unbound factor; which is
+    /// then bound to 0, unless another component binds it.
+    ///
     /// ## Parameters
     ///
     /// - `shape`: The shape to match against.
@@ -197,17 +638,122 @@ impl ShapePattern {
     ///
     /// ## Errors
     ///
-    /// Returns an error if the shape does not match the pattern.
+    /// Returns an error if the shape does not match the pattern,
+    /// or the product of a composite's bound factors overflows.
     ///
     /// ## Returns
     ///
     /// Returns a `ShapeMatch` if the shape matches the pattern.
-    #[allow(clippy::missing_panics_doc)]
     pub fn match_bindings<B: ShapeBindingSource>(
         &self,
         shape: &[usize],
         bindings: B,
     ) -> Result<ShapeMatch, ShapePatternError> {
+        self.match_bindings_with(shape, bindings, MatchOptions::default())
+    }
+
+    /// Check the bindings against `options`, before matching.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `UnusedBindings` or `UnboundDimensions`, as enabled by `options`.
+    pub fn check_bindings<B: ShapeBindingSource>(
+        &self,
+        bindings: &B,
+        options: MatchOptions,
+    ) -> Result<(), ShapePatternError> {
+        if !options.deny_unused_bindings && !options.require_bound {
+            return Ok(());
+        }
+        let names = self.dim_names();
+
+        if options.deny_unused_bindings {
+            // The binding each name resolves to, through enclosing scopes.
+            let used: Vec<Cow<str>> = names
+                .iter()
+                .filter_map(|name| {
+                    scope_chain(name)
+                        .into_iter()
+                        .find(|scoped| bindings.lookup_shape_binding(scoped).is_some())
+                })
+                .collect();
+            let unused: Vec<UnusedBinding> = bindings
+                .for_each_shape_binding()
+                .filter(|(name, _)| !used.iter().any(|used| used == name))
+                .map(|(name, _)| UnusedBinding {
+                    name: name.to_string(),
+                    suggestion: closest_name(name, names.iter().copied()).map(str::to_string),
+                })
+                .collect();
+            if !unused.is_empty() {
+                return Err(ShapePatternError::UnusedBindings {
+                    pattern: self.to_string(),
+                    unused,
+                });
+            }
+        }
+
+        if options.require_bound {
+            let unbound: Vec<String> = names
+                .iter()
+                .filter(|name| bindings.lookup_scoped_binding(name).is_none())
+                .map(|name| (*name).to_string())
+                .collect();
+            if !unbound.is_empty() {
+                return Err(ShapePatternError::UnboundDimensions {
+                    pattern: self.to_string(),
+                    names: unbound,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Assert that the `ShapeEx` matches a given shape, with `MatchOptions`.
+    ///
+    /// ## Parameters
+    ///
+    /// - `shape`: The shape to match against.
+    /// - `bindings`: The bindings to use for matching.
+    /// - `options`: The strictness checks to apply.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the bindings fail the checks enabled by `options`
+    /// (see `check_bindings()`), or the shape does not match the pattern.
+    ///
+    /// ## Returns
+    ///
+    /// Returns a `ShapeMatch` if the shape matches the pattern.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use burn_contracts::shapes::{MatchOptions, ShapePattern};
+    ///
+    /// let pattern = ShapePattern::parse("batch channels").unwrap();
+    /// let options = MatchOptions {
+    ///     deny_unused_bindings: true,
+    ///     ..MatchOptions::default()
+    /// };
+    /// let err = pattern
+    ///     .match_bindings_with(&[2, 3], &[("chanels", 3)], options)
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Unused bindings for \"batch channels\": \"chanels\" (did you mean \"channels\"?)"
+    /// );
+    /// ```
+    #[allow(clippy::missing_panics_doc)]
+    pub fn match_bindings_with<B: ShapeBindingSource>(
+        &self,
+        shape: &[usize],
+        bindings: B,
+        options: MatchOptions,
+    ) -> Result<ShapeMatch, ShapePatternError> {
+        self.check_bindings(&bindings, options)?;
+
         // FIXME: Reconsider result contents.
         // - We can skip returning the source shape.
         // - returned bindings should be an assoc vec OR fixed array?
@@ -215,125 +761,255 @@ impl ShapePattern {
         // - return ellipsis dims, locations; both?
         // - multi-pass to resolve composite bindings?
 
-        let bindings: HashMap<String, usize> = collect_binding_map(bindings);
+        // Bindings are looked up through the source, rather than collected;
+        // the full list is only materialized for error reporting.
+        let m = self
+            .match_dims_core(shape, |id| bindings.lookup_scoped_binding(id))
+            .map_err(|failure| match failure {
+                Failure::Match(failure) => failure.into_error(shape, self, &bindings),
+                Failure::Error(err) => err,
+            })?;
+
+        Ok(ShapeMatch {
+            shape: m.shape,
+            bindings: m.bindings,
+            ellipsis_range: m.ellipsis_range,
+        })
+    }
 
+    /// Match a shape of any `DimSize`, such as a symbolic shape.
+    ///
+    /// Constraints which cannot be decided, because they depend on unknown sizes,
+    /// become `DimMatch::residuals`; an unbound composite factor which cannot be
+    /// solved is bound to an unknown named after it. For `usize` shapes, this agrees
+    /// with `match_bindings()`.
+    ///
+    /// ## Parameters
+    ///
+    /// - `shape`: The shape to match against.
+    /// - `bindings`: The bindings to use for matching.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a `SymbolicMatchError` if the shape cannot match the pattern,
+    /// or an `Overflow` error.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use burn_contracts::shapes::{ShapePattern, SymDim};
+    ///
+    /// let shape: Vec<SymDim> = ["batch", "3", "seq*2"]
+    ///     .iter()
+    ///     .map(|dim| dim.parse().unwrap())
+    ///     .collect();
+    ///
+    /// let pattern = ShapePattern::parse("b c (t k)").unwrap();
+    /// let m = pattern.match_dims(&shape, &[("k", SymDim::from(2))]).unwrap();
+    /// let [b, t] = m.select(["b", "t"]);
+    /// assert_eq!((b.to_string(), t.to_string()), ("batch".to_string(), "seq".to_string()));
+    ///
+    /// let pattern = ShapePattern::parse("b 4 d").unwrap();
+    /// assert!(pattern.match_dims(&shape, &[]).is_err());
+    ///
+    /// // `seq*2` may or may not be 8.
+    /// let pattern = ShapePattern::parse("b c 8").unwrap();
+    /// let m = pattern.match_dims(&shape, &[]).unwrap();
+    /// assert_eq!(m.residuals[0].to_string(), "8 == seq*2");
+    /// ```
+    pub fn match_dims<D: DimSize>(
+        &self,
+        shape: &[D],
+        bindings: &[(&str, D)],
+    ) -> Result<DimMatch<D>, ShapePatternError> {
+        let lookup = |id: &str| {
+            scope_chain(id).iter().find_map(|scoped| {
+                bindings
+                    .iter()
+                    .find(|(name, _)| name == scoped)
+                    .map(|(_, value)| value.clone())
+            })
+        };
+        self.match_dims_core(shape, lookup)
+            .map_err(|failure| match failure {
+                Failure::Match(failure) => {
+                    let (details, message) = failure.into_parts();
+                    ShapePatternError::SymbolicMatchError {
+                        shape: shape.iter().map(ToString::to_string).collect(),
+                        pattern: self.to_string(),
+                        details,
+                        message,
+                    }
+                }
+                Failure::Error(err) => err,
+            })
+    }
+
+    /// Match a shape of any `DimSize`, looking up bindings with `lookup`.
+    fn match_dims_core<D: DimSize>(
+        &self,
+        shape: &[D],
+        lookup: impl Fn(&str) -> Option<D>,
+    ) -> Result<DimMatch<D>, Failure> {
         let dims = shape.len();
         let ellipsis_pos = self.ellipsis_pos();
-        let non_e_comps = match ellipsis_pos {
-            Some(_) => self.components.len() - 1,
-            None => self.components.len(),
-        };
-        if non_e_comps > dims {
-            return Err(ShapePatternError::MatchError {
-                shape: shape.to_vec(),
-                pattern: self.to_string(),
-                bindings: bindings.iter().map(|(k, v)| (k.clone(), *v)).collect(),
-                message: "Too few dimensions".to_string(),
-            });
-        }
+        check_rank(dims, self.components.len(), ellipsis_pos.is_some()).map_err(Failure::Match)?;
+        let non_e_comps = self.components.len() - usize::from(ellipsis_pos.is_some());
         let ellipsis_range = ellipsis_pos.map(|pos| pos..pos + dims - non_e_comps);
 
-        let mut export = HashMap::new();
+        let mut export: HashMap<String, D> = HashMap::new();
+        let mut residuals = Vec::new();
+        // Factors of zero-sized composites; bound to 0 unless bound elsewhere.
+        let mut indeterminate: Vec<&String> = Vec::new();
 
-        fn readthrough_lookup(
-            bindings: &HashMap<String, usize>,
-            target: &mut HashMap<String, usize>,
-            id: &str,
-        ) -> Option<usize> {
+        let readthrough_lookup = |target: &mut HashMap<String, D>, id: &str| -> Option<D> {
             match target.get(id) {
-                Some(value) => Some(*value),
-                None => match bindings.get(id) {
-                    Some(value) => {
-                        target.insert(id.to_string(), *value);
-                        Some(*value)
-                    }
-                    None => None,
-                },
+                Some(value) => Some(value.clone()),
+                None => {
+                    let value = lookup(id)?;
+                    target.insert(id.to_string(), value.clone());
+                    Some(value)
+                }
             }
-        }
+        };
 
         let mut i = 0;
-        for component in &self.components {
-            let dim_shape = shape[i];
-            match component {
-                PatternComponent::Ellipsis => {
-                    i = ellipsis_range.clone().unwrap().end;
+        for (idx, component) in self.components.iter().enumerate() {
+            if let PatternComponent::Ellipsis = component {
+                i = ellipsis_range.clone().unwrap().end;
+                continue;
+            }
+
+            let dim_shape = &shape[i];
+            let failure = |kind, expected: Option<&D>, message| {
+                Failure::Match(MatchFailure {
+                    kind,
+                    component: Some(idx),
+                    axes: i..i + 1,
+                    expected: expected.and_then(D::to_usize),
+                    actual: dim_shape.to_usize(),
+                    message,
+                })
+            };
+            // Require `expected == dim_shape`; an undecided equation is a residual.
+            let mut equate = |expected: D, label: &dyn Display| match expected.decide_eq(dim_shape)
+            {
+                Some(true) => Ok(()),
+                Some(false) => Err(failure(
+                    MatchErrorKind::ConstraintMismatch,
+                    Some(&expected),
+                    format!("Constraint Mismatch @{label}: {expected} != {dim_shape}"),
+                )),
+                None => {
+                    residuals.push(Residual {
+                        component: idx,
+                        expected,
+                        actual: dim_shape.clone(),
+                    });
+                    Ok(())
                 }
-                PatternComponent::Dim(id) => {
-                    match readthrough_lookup(&bindings, &mut export, id) {
-                        Some(bound_value) => {
-                            if bound_value != dim_shape {
-                                let message = format!(
-                                    "Constraint Mismatch @{id}: {bound_value} != {dim_shape}"
-                                );
-
-                                return Err(ShapePatternError::MatchError {
-                                    shape: shape.to_vec(),
-                                    pattern: self.to_string(),
-                                    bindings: bindings
-                                        .iter()
-                                        .map(|(k, v)| (k.clone(), *v))
-                                        .collect(),
-                                    message,
-                                });
-                            }
-                        }
-                        None => {
-                            export.insert(id.clone(), dim_shape);
-                        }
+            };
+
+            match component {
+                PatternComponent::Ellipsis => unreachable!(),
+                PatternComponent::Literal(size) => equate(D::from_usize(*size), size)?,
+                PatternComponent::Dim(id) => match readthrough_lookup(&mut export, id) {
+                    Some(bound_value) => equate(bound_value, id)?,
+                    None => {
+                        export.insert(id.clone(), dim_shape.clone());
                     }
-                    i += 1;
-                }
+                },
                 PatternComponent::Composite(ids) => {
-                    let mut acc = 1;
-                    let mut unbound: Option<String> = None;
+                    let mut acc = D::from_usize(1);
+                    let mut unbound: Option<&String> = None;
                     for factor in ids {
-                        if let Some(value) = readthrough_lookup(&bindings, &mut export, factor) {
-                            acc *= value;
-                        } else {
-                            if unbound.is_some() {
-                                return Err(ShapePatternError::MatchError {
-                                    shape: shape.to_vec(),
+                        if let Some(value) = readthrough_lookup(&mut export, factor) {
+                            acc = acc.checked_mul(&value).ok_or_else(|| {
+                                Failure::Error(ShapePatternError::Overflow {
                                     pattern: self.to_string(),
-                                    bindings: bindings
+                                    shape: shape.iter().map(ToString::to_string).collect(),
+                                    component: idx,
+                                    factors: ids
                                         .iter()
-                                        .map(|(k, v)| (k.clone(), *v))
+                                        .filter_map(|id| {
+                                            Some((id.clone(), export.get(id)?.to_usize()?))
+                                        })
                                         .collect(),
-                                    message: "Multiple unbound factors in composite".to_string(),
-                                });
+                                })
+                            })?;
+                        } else {
+                            if unbound.is_some() {
+                                return Err(failure(
+                                    MatchErrorKind::Underdetermined,
+                                    None,
+                                    "Multiple unbound factors in composite".to_string(),
+                                ));
                             }
-                            unbound = Some(factor.clone());
+                            unbound = Some(factor);
                         }
                     }
-                    if let Some(factor) = unbound {
-                        if dim_shape % acc != 0 {
-                            return Err(ShapePatternError::MatchError {
-                                shape: shape.to_vec(),
-                                pattern: self.to_string(),
-                                bindings: bindings.iter().map(|(k, v)| (k.clone(), *v)).collect(),
-                                message: format!(
-                                    "Composite factor \"{factor}\" * {acc} != shape {dim_shape}",
-                                ),
-                            });
+                    match unbound {
+                        Some(factor) => {
+                            let non_divisible = || {
+                                failure(
+                                    MatchErrorKind::NonDivisibleComposite,
+                                    Some(&acc),
+                                    format!(
+                                        "Composite factor \"{factor}\" * {acc} != shape {dim_shape}",
+                                    ),
+                                )
+                            };
+                            match dim_shape.solve_factor(&acc) {
+                                Solved::Factor(value) => {
+                                    export.insert(factor.clone(), value);
+                                }
+                                // A zero product matches a zero dim, for any factor.
+                                Solved::Indeterminate => indeterminate.push(factor),
+                                Solved::Impossible => return Err(non_divisible()),
+                                Solved::Undecided => {
+                                    let unknown = D::unknown(factor).ok_or_else(non_divisible)?;
+                                    let expected =
+                                        unknown.checked_mul(&acc).ok_or_else(non_divisible)?;
+                                    residuals.push(Residual {
+                                        component: idx,
+                                        expected,
+                                        actual: dim_shape.clone(),
+                                    });
+                                    export.insert(factor.clone(), unknown);
+                                }
+                            }
                         }
-                        export.insert(factor, dim_shape / acc);
+                        None => equate(acc, component)?,
                     }
-                    i += 1;
                 }
             }
+            i += 1;
+        }
+        for factor in indeterminate {
+            export
+                .entry(factor.clone())
+                .or_insert_with(|| D::from_usize(0));
         }
 
-        Ok(ShapeMatch {
+        Ok(DimMatch {
             shape: shape.to_vec(),
             bindings: export,
+            residuals,
             ellipsis_range,
         })
     }
 }
 
+/// Why a match failed; reported as a `ShapePatternError` by the caller, which knows the shape type.
+enum Failure {
+    Match(MatchFailure),
+    Error(ShapePatternError),
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::shapes::SymDim;
     use std::error::Error;
 
     #[test]
@@ -402,4 +1078,435 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_layered_bindings() -> Result<(), Box<dyn Error>> {
+        let defaults = [("p", 4), ("c", 3)];
+        let overrides = crate::shapes::bindings::from_fn(|name| (name == "p").then_some(2));
+
+        let [h, w] = ShapePattern::cached_parse("b (h p) (w p) c")?
+            .match_bindings(&[1, 6, 8, 3], (&overrides, &defaults))?
+            .select(["h", "w"]);
+
+        assert_eq!(h, 3);
+        assert_eq!(w, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_error_display() {
+        let err = ShapePattern::parse("b ... (h p w").unwrap_err();
+        assert_eq!(
+            err,
+            ShapePatternError::ParseError {
+                pattern: "b ... (h p w".to_string(),
+                span: 12..12,
+                expected: vec!["')'".to_string()],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Parse error for \"b ... (h p w\": expected ')'"
+        );
+        assert_eq!(
+            err.render(),
+            "Parse error for \"b ... (h p w\": expected ')'\n\
+             b ... (h p w\n\
+             \x20           ^"
+        );
+    }
+
+    fn match_details(
+        pattern: &str,
+        shape: &[usize],
+        bindings: &[(&str, usize)],
+    ) -> MatchDetails {
+        match ShapePattern::parse(pattern)
+            .unwrap()
+            .match_bindings(shape, bindings)
+        {
+            Err(ShapePatternError::MatchError { details, .. }) => *details,
+            other => panic!("expected a MatchError, found {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_rejects_extra_dims_and_bound_composites() {
+        // Both matched before the checks were added.
+        let pattern = ShapePattern::parse("b c").unwrap();
+        assert!(pattern.match_bindings(&[2, 3, 4], &[("b", 2)]).is_err());
+        assert!(
+            ShapePattern::parse("b ... c")
+                .unwrap()
+                .match_bindings(&[2, 3, 4], &[("b", 2)])
+                .is_ok()
+        );
+
+        let pattern = ShapePattern::parse("b (h p)").unwrap();
+        assert!(
+            pattern
+                .match_bindings(&[2, 9], &[("h", 2), ("p", 4)])
+                .is_err()
+        );
+        assert!(
+            pattern
+                .match_bindings(&[2, 8], &[("h", 2), ("p", 4)])
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_match_error_details() {
+        assert_eq!(
+            match_details("b ... c", &[2], &[]),
+            MatchDetails {
+                kind: MatchErrorKind::Rank,
+                component: None,
+                axes: 0..1,
+                expected: Some(2),
+                actual: Some(1),
+            }
+        );
+        assert_eq!(
+            match_details("b c", &[2, 3, 4], &[]),
+            MatchDetails {
+                kind: MatchErrorKind::Rank,
+                component: None,
+                axes: 0..3,
+                expected: Some(2),
+                actual: Some(3),
+            }
+        );
+        assert_eq!(
+            match_details("b ... c", &[2, 9, 9, 4], &[("c", 3)]),
+            MatchDetails {
+                kind: MatchErrorKind::ConstraintMismatch,
+                component: Some(2),
+                axes: 3..4,
+                expected: Some(3),
+                actual: Some(4),
+            }
+        );
+        assert_eq!(
+            match_details("b (h p)", &[2, 9], &[("h", 2), ("p", 4)]),
+            MatchDetails {
+                kind: MatchErrorKind::ConstraintMismatch,
+                component: Some(1),
+                axes: 1..2,
+                expected: Some(8),
+                actual: Some(9),
+            }
+        );
+        assert_eq!(
+            match_details("b (h p)", &[2, 9], &[("p", 4)]),
+            MatchDetails {
+                kind: MatchErrorKind::NonDivisibleComposite,
+                component: Some(1),
+                axes: 1..2,
+                expected: Some(4),
+                actual: Some(9),
+            }
+        );
+        assert_eq!(
+            match_details("b (h p)", &[2, 9], &[]),
+            MatchDetails {
+                kind: MatchErrorKind::Underdetermined,
+                component: Some(1),
+                axes: 1..2,
+                expected: None,
+                actual: Some(9),
+            }
+        );
+    }
+
+    #[test]
+    fn test_render_alignment() {
+        let pattern = ShapePattern::parse("b ... (h p) (w p) c").unwrap();
+        assert_eq!(
+            pattern
+                .render_alignment(&[2, 9, 9, 80, 40, 3], Some(3))
+                .unwrap(),
+            "b  ...  (h p)  (w p)  c\n\
+             2  9 9  80     40     3\n\
+             \x20              ^^^^^"
+        );
+        assert_eq!(
+            pattern.render_alignment(&[2, 80, 40, 3], None).unwrap(),
+            "b  ...  (h p)  (w p)  c\n\
+             2       80     40     3"
+        );
+        assert_eq!(pattern.render_alignment(&[2, 3], None), None);
+    }
+
+    #[test]
+    fn test_match_error_display() {
+        let err = ShapePattern::parse("b (h p) c")
+            .unwrap()
+            .match_bindings(&[2, 9, 3], &[("p", 4)])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Shape \"[2, 9, 3]\" !~= \"b (h p) c\" with [(\"p\", 4)]: \
+             Composite factor \"h\" * 4 != shape 9"
+        );
+        assert_eq!(
+            err.render(),
+            "Shape \"[2, 9, 3]\" !~= \"b (h p) c\" with [(\"p\", 4)]: \
+             Composite factor \"h\" * 4 != shape 9\n\
+             b  (h p)  c\n\
+             2  9      3\n\
+             \x20  ^^^^^"
+        );
+
+        let ShapePatternError::MatchError { components, .. } = &err else {
+            panic!("expected a MatchError: {err}");
+        };
+        assert_eq!(components.as_ref(), ["b", "(h p)", "c"]);
+    }
+
+    #[test]
+    fn test_dim_names() {
+        let pattern = ShapePattern::parse("b ... (h p) (w p) c").unwrap();
+        assert_eq!(pattern.dim_names(), ["b", "h", "p", "w", "c"]);
+    }
+
+    #[test]
+    fn test_strict_unused_bindings() {
+        let pattern = ShapePattern::parse("batch (height p) channels").unwrap();
+        let shape = [2, 12, 3];
+        let bindings = [("p", 4), ("chanels", 3), ("depth", 1)];
+
+        // Lenient matching ignores the typo, and binds "channels" from the shape.
+        let m = pattern.match_bindings(&shape, &bindings).unwrap();
+        assert_eq!(m.bindings["channels"], 3);
+
+        let options = MatchOptions {
+            deny_unused_bindings: true,
+            ..MatchOptions::default()
+        };
+        assert_eq!(
+            pattern
+                .match_bindings_with(&shape, &bindings, options)
+                .unwrap_err(),
+            ShapePatternError::UnusedBindings {
+                pattern: "batch (height p) channels".to_string(),
+                unused: vec![
+                    UnusedBinding {
+                        name: "chanels".to_string(),
+                        suggestion: Some("channels".to_string()),
+                    },
+                    UnusedBinding {
+                        name: "depth".to_string(),
+                        suggestion: None,
+                    },
+                ],
+            }
+        );
+
+        assert!(
+            pattern
+                .match_bindings_with(&shape, &[("p", 4), ("channels", 3)], options)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_strict_require_bound() {
+        let pattern = ShapePattern::parse("b (h p) c").unwrap();
+        let options = MatchOptions {
+            require_bound: true,
+            ..MatchOptions::default()
+        };
+
+        let err = pattern
+            .match_bindings_with(&[2, 12, 3], &[("p", 4), ("b", 2)], options)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ShapePatternError::UnboundDimensions {
+                pattern: "b (h p) c".to_string(),
+                names: vec!["h".to_string(), "c".to_string()],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Unbound dimensions for \"b (h p) c\": [\"h\", \"c\"]"
+        );
+
+        let bindings = [("b", 2), ("h", 3), ("p", 4), ("c", 3)];
+        assert!(
+            pattern
+                .match_bindings_with(&[2, 12, 3], &bindings, MatchOptions::STRICT)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_zero_sized_dims() {
+        let pattern = ShapePattern::parse("(b t) d").unwrap();
+
+        // An empty batch: the factor is solved as 0.
+        let m = pattern.match_bindings(&[0, 4], &[("t", 5)]).unwrap();
+        assert_eq!(m.select(["b", "t", "d"]), [0, 5, 4]);
+
+        // A zero factor binds the other factor to 0.
+        let m = pattern.match_bindings(&[0, 4], &[("b", 0)]).unwrap();
+        assert_eq!(m.select(["b", "t"]), [0, 0]);
+
+        // ... unless another component binds it.
+        let m = ShapePattern::parse("(b t) t")
+            .unwrap()
+            .match_bindings(&[0, 7], &[("b", 0)])
+            .unwrap();
+        assert_eq!(m.select(["t"]), [7]);
+
+        // A zero factor only matches a zero dim.
+        let err = pattern.match_bindings(&[3, 4], &[("b", 0)]).unwrap_err();
+        let ShapePatternError::MatchError { details, .. } = err else {
+            panic!("expected a MatchError");
+        };
+        assert_eq!(details.kind, MatchErrorKind::NonDivisibleComposite);
+
+        let err = pattern
+            .match_bindings(&[3, 4], &[("b", 0), ("t", 2)])
+            .unwrap_err();
+        let ShapePatternError::MatchError { details, .. } = err else {
+            panic!("expected a MatchError");
+        };
+        assert_eq!(details.kind, MatchErrorKind::ConstraintMismatch);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let pattern = ShapePattern::parse("(b t) d").unwrap();
+        let err = pattern
+            .match_bindings(&[6, 4], &[("b", usize::MAX), ("t", 2)])
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ShapePatternError::Overflow {
+                pattern: "(b t) d".to_string(),
+                shape: vec!["6".to_string(), "4".to_string()],
+                component: 0,
+                factors: vec![("b".to_string(), usize::MAX), ("t".to_string(), 2)],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Overflow matching \"(b t) d\" against [6, 4]: the bound factors [(\"b\", {0}), (\"t\", 2)] of component 0 overflow usize",
+                usize::MAX
+            )
+        );
+    }
+
+    #[test]
+    fn test_match_dims_agrees_with_match_bindings() {
+        type Case<'a> = (&'a str, &'a [usize], &'a [(&'a str, usize)]);
+        let cases: &[Case] = &[
+            ("b ... (h p) (w p) c", &[2, 9, 9, 80, 40, 3], &[("p", 4)]),
+            ("b ... (h p) c", &[2, 81, 3], &[("p", 4)]),
+            ("b (h p)", &[2, 9], &[("h", 2), ("p", 4)]),
+            ("a b", &[1, 2, 3], &[]),
+            ("b 3 h", &[2, 4, 4], &[]),
+            ("(b t) d", &[0, 4], &[("b", 0)]),
+        ];
+        for (pattern, shape, bindings) in cases {
+            let pattern = ShapePattern::parse(pattern).unwrap();
+            let expected = pattern.match_bindings(shape, *bindings);
+            let actual = pattern.match_dims(shape, bindings);
+            match (expected, actual) {
+                (Ok(expected), Ok(actual)) => {
+                    assert_eq!(expected.bindings, actual.bindings);
+                    assert!(actual.residuals.is_empty());
+                }
+                (
+                    Err(ShapePatternError::MatchError {
+                        details: expected,
+                        message: expected_message,
+                        ..
+                    }),
+                    Err(ShapePatternError::SymbolicMatchError {
+                        details: actual,
+                        message: actual_message,
+                        ..
+                    }),
+                ) => {
+                    assert_eq!(expected, actual);
+                    assert_eq!(expected_message, actual_message);
+                }
+                (expected, actual) => panic!("{expected:?} != {actual:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_match_symbolic_dims() {
+        let sym =
+            |dims: &[&str]| -> Vec<SymDim> { dims.iter().map(|d| d.parse().unwrap()).collect() };
+
+        // Factors divide out symbolically.
+        let pattern = ShapePattern::parse("b (h k) (t two)").unwrap();
+        let m = pattern
+            .match_dims(
+                &sym(&["batch", "heads*64", "seq*2"]),
+                &[("k", SymDim::from(64)), ("two", SymDim::from(2))],
+            )
+            .unwrap();
+        assert_eq!(
+            m.select(["b", "h", "t"]).map(|d| d.to_string()),
+            ["batch", "heads", "seq"]
+        );
+        assert!(m.residuals.is_empty());
+
+        // Undecided factors become unknowns, with residual equations.
+        let pattern = ShapePattern::parse("b (t k) b").unwrap();
+        let m = pattern
+            .match_dims(&sym(&["n", "seq", "4"]), &[("k", SymDim::from(2))])
+            .unwrap();
+        assert_eq!(m.bindings["t"], SymDim::symbol("t"));
+        let residuals: Vec<String> = m.residuals.iter().map(ToString::to_string).collect();
+        assert_eq!(residuals, ["t*2 == seq", "n == 4"]);
+
+        // Constants still conflict.
+        let err = pattern
+            .match_dims(&sym(&["n", "6", "4"]), &[("k", SymDim::from(4))])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Shape \"[n, 6, 4]\" !~= \"b (t k) b\": Composite factor \"t\" * 4 != shape 6"
+        );
+    }
+
+    #[test]
+    fn test_scoped_names() {
+        let pattern = ShapePattern::parse("b enc.d (dec.h dec.d)").unwrap();
+        let bindings = [("d", 8), ("dec.d", 4)];
+
+        let m = pattern.match_bindings(&[2, 8, 12], &bindings).unwrap();
+        assert_eq!(m.select(["enc.d", "dec.h", "dec.d"]), [8, 3, 4]);
+        assert!(pattern.match_bindings(&[2, 7, 12], &bindings).is_err());
+
+        // Both bindings are used; `d` through the `enc` scope.
+        assert!(
+            pattern
+                .match_bindings_with(
+                    &[2, 8, 12],
+                    &bindings,
+                    MatchOptions {
+                        deny_unused_bindings: true,
+                        ..MatchOptions::default()
+                    }
+                )
+                .is_ok()
+        );
+
+        let pattern = ShapePattern::parse("höhe breite").unwrap();
+        let [h] = pattern
+            .match_bindings(&[3, 4], &[("höhe", 3)])
+            .unwrap()
+            .select(["höhe"]);
+        assert_eq!(h, 3);
+    }
 }
@@ -0,0 +1,394 @@
+//! Inferring a `ShapePattern` from example shapes.
+use crate::shapes::exp::{PatternComponent, ShapePattern, ShapePatternError};
+use burn_contracts_grammar::{Component, parse_components};
+use std::collections::BTreeMap;
+
+/// Hints for `ShapePattern::infer()`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InferHints {
+    /// Names for the varying dimensions, in order of first appearance;
+    /// further dimensions are named `d0`, `d1`, ...
+    ///
+    /// Each must be a distinct identifier, such as `b` or `enc.d_model`.
+    pub names: Vec<String>,
+
+    /// Detect a common factor of the varying dimensions, as a composite.
+    pub composites: bool,
+}
+
+impl Default for InferHints {
+    fn default() -> Self {
+        Self {
+            names: Vec::new(),
+            composites: true,
+        }
+    }
+}
+
+/// The result of `ShapePattern::infer()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inference {
+    /// The inferred pattern; matched by every example.
+    pub pattern: ShapePattern,
+
+    /// The detected composite factors, and their sizes; bind these when matching.
+    pub factors: BTreeMap<String, usize>,
+
+    /// The confidence in the pattern, in `[0, 1]`.
+    ///
+    /// This is the support for the weakest claim the pattern makes beyond the examples:
+    /// a fixed rank or size, dimensions sharing a name, or a common factor.
+    /// Each is less likely to hold by chance as the number of distinct examples grows;
+    /// a single example gives no confidence.
+    pub confidence: f64,
+}
+
+/// The support for a claim backed by `n` independent observations,
+/// each of which would hold by chance with probability `p`.
+fn support(
+    p: f64,
+    n: usize,
+) -> f64 {
+    1.0 - p.powi(i32::try_from(n).unwrap_or(i32::MAX))
+}
+
+fn gcd(
+    a: usize,
+    b: usize,
+) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// The number of distinct values.
+fn distinct(values: &[usize]) -> usize {
+    let mut values = values.to_vec();
+    values.sort_unstable();
+    values.dedup();
+    values.len()
+}
+
+/// The columns of the examples; axes `..prefix` from the front, and `suffix` axes from the back.
+fn columns(
+    examples: &[&[usize]],
+    prefix: usize,
+    suffix: usize,
+) -> Vec<Vec<usize>> {
+    let front = (0..prefix).map(|i| examples.iter().map(|shape| shape[i]).collect());
+    let back = (0..suffix).map(|j| {
+        examples
+            .iter()
+            .map(|shape| shape[shape.len() - suffix + j])
+            .collect()
+    });
+    front.chain(back).collect()
+}
+
+/// Pick a name not in `used`, from `base`, `base1`, `base2`, ...
+fn fresh_name(
+    base: &str,
+    used: &[String],
+) -> String {
+    (0..)
+        .map(|i| {
+            if i == 0 {
+                base.to_string()
+            } else {
+                format!("{base}{i}")
+            }
+        })
+        .find(|name| !used.contains(name))
+        .unwrap_or_default()
+}
+
+/// Check that the hint names are distinct identifiers;
+/// so each names its own group, and the pattern parses back.
+fn check_hint_names(names: &[String]) -> Result<(), ShapePatternError> {
+    for (idx, name) in names.iter().enumerate() {
+        let message = if !matches!(
+            parse_components(name).as_deref(),
+            Ok([Component::Dim(dim)]) if dim == name
+        ) {
+            format!("Hint name \"{name}\" is not an identifier")
+        } else if names[..idx].contains(name) {
+            format!("Hint name \"{name}\" is given more than once")
+        } else {
+            continue;
+        };
+        return Err(ShapePatternError::InvalidPattern {
+            pattern: String::new(),
+            message,
+        });
+    }
+    Ok(())
+}
+
+impl ShapePattern {
+    /// Infer the tightest pattern matching all of the example shapes.
+    ///
+    /// - Dimensions which are constant across the examples become fixed sizes.
+    /// - Dimensions which vary together share a name.
+    /// - If the rank varies, the pattern has an ellipsis; placed to keep the most
+    ///   fixed sizes on either side of it.
+    /// - If several varying dimensions share a common factor, they become composites
+    ///   with that factor; see `Inference::factors`.
+    ///
+    /// ## Parameters
+    ///
+    /// - `examples`: The observed shapes.
+    /// - `hints`: Names for the inferred dimensions, and which structure to detect.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if there are no examples; or the hint names are not distinct identifiers.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use burn_contracts::shapes::{InferHints, ShapePattern};
+    ///
+    /// let inference = ShapePattern::infer(
+    ///     &[&[2, 3, 32, 32], &[5, 3, 64, 64], &[1, 3, 48, 48]],
+    ///     &InferHints {
+    ///         names: vec!["b".to_string(), "h".to_string()],
+    ///         ..Default::default()
+    ///     },
+    /// )
+    /// .unwrap();
+    /// assert_eq!(inference.pattern.to_string(), "b 3 h h");
+    /// assert!(inference.confidence > 0.7);
+    /// ```
+    pub fn infer(
+        examples: &[&[usize]],
+        hints: &InferHints,
+    ) -> Result<Inference, ShapePatternError> {
+        check_hint_names(&hints.names)?;
+        let (Some(min_rank), Some(max_rank)) = (
+            examples.iter().map(|shape| shape.len()).min(),
+            examples.iter().map(|shape| shape.len()).max(),
+        ) else {
+            return Err(ShapePatternError::InvalidPattern {
+                pattern: String::new(),
+                message: "Cannot infer a pattern from no examples".to_string(),
+            });
+        };
+        let mut distinct_shapes = examples.to_vec();
+        distinct_shapes.sort_unstable();
+        distinct_shapes.dedup();
+        let n = distinct_shapes.len();
+
+        // Split the common axes around the ellipsis, keeping the most constant columns;
+        // ties prefer a longer prefix.
+        let is_constant = |values: &Vec<usize>| values.iter().all(|v| *v == values[0]);
+        let ellipsis = min_rank != max_rank;
+        let prefix = if ellipsis {
+            (0..=min_rank)
+                .max_by_key(|prefix| {
+                    columns(examples, *prefix, min_rank - prefix)
+                        .iter()
+                        .filter(|values| is_constant(values))
+                        .count()
+                })
+                .unwrap_or(0)
+        } else {
+            min_rank
+        };
+        let columns = columns(examples, prefix, min_rank - prefix);
+
+        let mut confidence: f64 = 1.0;
+        if !ellipsis || columns.iter().any(is_constant) {
+            // A fixed rank, or fixed sizes.
+            confidence = confidence.min(support(0.5, n - 1));
+        }
+
+        // Group varying columns with identical values, in order of first appearance.
+        let mut groups: Vec<&Vec<usize>> = Vec::new();
+        for values in columns.iter().filter(|values| !is_constant(values)) {
+            if !groups.contains(&values) {
+                groups.push(values);
+            }
+        }
+        let mut names: Vec<String> = Vec::new();
+        for idx in 0..groups.len() {
+            let name = match hints.names.get(idx) {
+                Some(name) => name.clone(),
+                None => fresh_name(&format!("d{}", idx - hints.names.len()), &names),
+            };
+            names.push(name);
+        }
+        for group in &groups {
+            let shared = columns.iter().filter(|values| values == group).count();
+            if shared > 1 {
+                confidence = confidence.min(support(0.5, distinct(group) - 1));
+            }
+        }
+
+        // A common factor, shared by at least two groups.
+        let divisors: Vec<usize> = groups
+            .iter()
+            .map(|values| values.iter().fold(0, |acc, v| gcd(acc, *v)))
+            .collect();
+        let mut factors = BTreeMap::new();
+        let mut factor = None;
+        if hints.composites && divisors.iter().filter(|d| **d > 1).count() > 1 {
+            let common = divisors
+                .iter()
+                .filter(|d| **d > 1)
+                .fold(0, |acc, d| gcd(acc, *d));
+            if common > 1 {
+                let name = fresh_name("p", &names);
+                factors.insert(name.clone(), common);
+                let observed: Vec<usize> = groups
+                    .iter()
+                    .zip(&divisors)
+                    .filter(|(_, d)| **d > 1)
+                    .flat_map(|(values, _)| values.iter().map(|v| v / common))
+                    .collect();
+                #[allow(clippy::cast_precision_loss)]
+                let chance = 1.0 / common as f64;
+                confidence = confidence.min(support(chance, distinct(&observed) - 1));
+                factor = Some(name);
+            }
+        }
+
+        let mut components: Vec<PatternComponent> = columns
+            .iter()
+            .map(|values| {
+                if is_constant(values) {
+                    return PatternComponent::Literal(values[0]);
+                }
+                let idx = groups.iter().position(|g| *g == values).unwrap_or(0);
+                match &factor {
+                    Some(p) if divisors[idx] > 1 => {
+                        PatternComponent::Composite(vec![names[idx].clone(), p.clone()])
+                    }
+                    _ => PatternComponent::Dim(names[idx].clone()),
+                }
+            })
+            .collect();
+        if ellipsis {
+            components.insert(prefix, PatternComponent::Ellipsis);
+        }
+
+        Ok(Inference {
+            pattern: ShapePattern::new(components)?,
+            factors,
+            confidence,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(examples: &[&[usize]]) -> Inference {
+        ShapePattern::infer(examples, &InferHints::default()).unwrap()
+    }
+
+    #[test]
+    fn test_infer_fixed_rank() {
+        let inference = infer(&[&[2, 3, 7], &[4, 3, 9], &[5, 3, 9]]);
+        assert_eq!(inference.pattern.to_string(), "d0 3 d1");
+        assert!(inference.factors.is_empty());
+        assert!((inference.confidence - 0.75).abs() < 1e-9);
+
+        assert!(
+            inference
+                .pattern
+                .match_bindings(&[4, 3, 9], &[] as &[(&str, usize)])
+                .is_ok()
+        );
+
+        // A single example fixes everything, with no confidence.
+        let inference = infer(&[&[2, 3]]);
+        assert_eq!(inference.pattern.to_string(), "2 3");
+        assert!(inference.confidence.abs() < 1e-9);
+
+        assert!(ShapePattern::infer(&[], &InferHints::default()).is_err());
+    }
+
+    #[test]
+    fn test_infer_shared_names() {
+        let inference = infer(&[&[2, 32, 32], &[2, 64, 64], &[3, 17, 17]]);
+        assert_eq!(inference.pattern.to_string(), "d0 d1 d1");
+        assert!((inference.confidence - 0.75).abs() < 1e-9);
+
+        let inference = ShapePattern::infer(
+            &[&[2, 5, 7], &[3, 5, 8]],
+            &InferHints {
+                names: vec!["batch".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(inference.pattern.to_string(), "batch 5 d0");
+    }
+
+    #[test]
+    fn test_infer_ellipsis() {
+        let inference = infer(&[&[2, 3, 4], &[5, 3, 7, 8, 4], &[9, 3, 4]]);
+        assert_eq!(inference.pattern.to_string(), "d0 3 ... 4");
+
+        let inference = infer(&[&[4], &[6, 4], &[2, 2, 4]]);
+        assert_eq!(inference.pattern.to_string(), "... 4");
+
+        let inference = infer(&[&[], &[1]]);
+        assert_eq!(inference.pattern.to_string(), "...");
+        assert!((inference.confidence - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_infer_composites() {
+        let examples: &[&[usize]] = &[&[2, 512, 128], &[2, 768, 192], &[7, 256, 64]];
+        let inference = infer(examples);
+        assert_eq!(inference.pattern.to_string(), "d0 (d1 p) (d2 p)");
+        assert_eq!(inference.factors, [("p".to_string(), 64)].into());
+        for shape in examples {
+            assert!(
+                inference
+                    .pattern
+                    .match_bindings(shape, &[("p", 64)])
+                    .is_ok()
+            );
+        }
+
+        let inference = ShapePattern::infer(
+            examples,
+            &InferHints {
+                composites: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(inference.pattern.to_string(), "d0 d1 d2");
+    }
+
+    #[test]
+    fn test_infer_rejects_bad_hints() {
+        let examples: &[&[usize]] = &[&[2, 3], &[5, 4]];
+        let hints = |names: &[&str]| InferHints {
+            names: names.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        };
+
+        for names in [
+            &["x", "x"][..],
+            &["b c"],
+            &["1b"],
+            &["(h p)"],
+            &["..."],
+            &[" b"],
+        ] {
+            assert!(
+                matches!(
+                    ShapePattern::infer(examples, &hints(names)),
+                    Err(ShapePatternError::InvalidPattern { .. })
+                ),
+                "{names:?}"
+            );
+        }
+
+        let inference = ShapePattern::infer(examples, &hints(&["b", "enc.h"])).unwrap();
+        assert_eq!(inference.pattern.to_string(), "b enc.h");
+    }
+}
@@ -0,0 +1,133 @@
+use crate::shapes::exp::ShapePattern;
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+/// A `ShapePattern` which is parsed once, on first use.
+///
+/// Intended for `static` items; so hot code can hold a parsed pattern
+/// without the string hash and cache lookup of `ShapePattern::cached_parse()`.
+///
+/// A `const` item also works, but each use of a `const` is a fresh value,
+/// which will be parsed again.
+///
+/// ## Panics
+///
+/// Dereferencing panics with the `ShapePatternError` if the pattern is malformed.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shapes::LazyShapePattern;
+///
+/// static PATCHES: LazyShapePattern = LazyShapePattern::new("b (h p) (w p) c");
+///
+/// let [h, w] = PATCHES
+///     .match_bindings(&[2, 20, 8, 3], &[("p", 4)])
+///     .unwrap()
+///     .select(["h", "w"]);
+/// assert_eq!((h, w), (5, 2));
+/// ```
+#[derive(Debug)]
+pub struct LazyShapePattern {
+    source: &'static str,
+    pattern: OnceLock<ShapePattern>,
+}
+
+impl LazyShapePattern {
+    /// Create a new `LazyShapePattern`; the source is not parsed until first use.
+    #[must_use]
+    pub const fn new(source: &'static str) -> Self {
+        Self {
+            source,
+            pattern: OnceLock::new(),
+        }
+    }
+
+    /// Get the pattern source string.
+    #[must_use]
+    pub fn source(&self) -> &'static str {
+        self.source
+    }
+
+    /// Get the parsed pattern, parsing it on first use.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the pattern is malformed.
+    #[must_use]
+    pub fn get(&self) -> &ShapePattern {
+        self.pattern
+            .get_or_init(|| match ShapePattern::parse(self.source) {
+                Ok(pattern) => pattern,
+                Err(err) => panic!("{}", err.render()),
+            })
+    }
+}
+
+impl Deref for LazyShapePattern {
+    type Target = ShapePattern;
+
+    fn deref(&self) -> &Self::Target {
+        self.get()
+    }
+}
+
+/// Get a `&'static ShapePattern`, parsed once on first use.
+///
+/// Expands to a hidden `static LazyShapePattern`.
+///
+/// ## Panics
+///
+/// Panics with the `ShapePatternError` if the pattern is malformed.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shape_pattern;
+///
+/// let [b] = shape_pattern!("b ... c")
+///     .match_bindings(&[2, 3, 4], &[("c", 4)])
+///     .unwrap()
+///     .select(["b"]);
+/// assert_eq!(b, 2);
+/// ```
+#[macro_export]
+macro_rules! shape_pattern {
+    ($pattern:expr) => {{
+        static PATTERN: $crate::shapes::LazyShapePattern =
+            $crate::shapes::LazyShapePattern::new($pattern);
+        PATTERN.get()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy_pattern() {
+        static PATTERN: LazyShapePattern = LazyShapePattern::new("b ... c");
+
+        assert_eq!(PATTERN.source(), "b ... c");
+        assert_eq!(PATTERN.to_string(), "b ... c");
+        assert!(std::ptr::eq(PATTERN.get(), PATTERN.get()));
+    }
+
+    #[test]
+    fn test_shape_pattern_macro() {
+        fn pattern() -> &'static ShapePattern {
+            shape_pattern!("b (h p)")
+        }
+
+        assert_eq!(pattern().to_string(), "b (h p)");
+        assert!(std::ptr::eq(pattern(), pattern()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Parse error for \"b (h\"")]
+    fn test_malformed() {
+        static PATTERN: LazyShapePattern = LazyShapePattern::new("b (h");
+
+        let _ = PATTERN.get();
+    }
+}
@@ -1,7 +1,33 @@
+mod algebra;
+mod alias;
 pub mod bindings;
 
+pub mod cache;
+pub mod compiled;
+pub mod contract;
+#[cfg(feature = "miette")]
+mod diagnostic;
 pub mod exp;
+mod infer;
+mod lazy;
 mod parser;
+pub mod ranked;
+pub mod registry;
+#[cfg(feature = "serde")]
+pub mod serialization;
+mod spec;
+mod suggest;
+pub mod symbolic;
 
+pub use algebra::Unifier;
+pub use alias::PatternAliases;
 pub use bindings::ShapeBindingSource;
+pub use compiled::CompiledPattern;
+pub use contract::ContractShape;
 pub use exp::*;
+pub use infer::{InferHints, Inference};
+pub use lazy::LazyShapePattern;
+pub use ranked::{PatternHandle, RankedPattern};
+pub use registry::ContractRegistry;
+pub use spec::{ElementSpec, ExactDType, TensorSpec};
+pub use symbolic::{DimMatch, DimSize, Residual, SymDim};
@@ -1,26 +1,10 @@
 use crate::shapes::exp::{PatternComponent, ShapePattern, ShapePatternError};
-use nom::IResult;
-use nom::Parser;
-use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, alphanumeric1, multispace0, multispace1};
-use nom::combinator::{map, recognize};
-use nom::multi::{many0, many1, separated_list1};
-use nom::sequence::{delimited, pair, preceded, terminated};
+use burn_contracts_grammar::{Component, parse_components};
 
-use once_cell::sync::Lazy;
-
-use quick_cache::sync::Cache;
-
-fn parse_cache() -> &'static Cache<String, ShapePattern> {
-    static PARSE_CACHE: Lazy<Cache<String, ShapePattern>> = Lazy::new(|| Cache::new(1000));
-    &PARSE_CACHE
-}
-
-/// Parse a `ShapePattern` and cache the result.
+/// Parse a `ShapePattern`.
 ///
-/// This function is thread-safe; and uses a process-wide cache,
-/// so expressions should be parsed-once and shared.
+/// The grammar itself lives in `burn-contracts-grammar`,
+/// which is shared with the compile-time `pattern!` macro.
 ///
 /// ## Parameters
 ///
@@ -30,137 +14,47 @@ fn parse_cache() -> &'static Cache<String, ShapePattern> {
 ///
 /// Returns an error if the input string cannot be parsed;
 /// or the pattern is invalid.
-pub fn cached_parse_shape_pattern(input: &str) -> Result<ShapePattern, ShapePatternError> {
-    parse_cache().get_or_insert_with(input, || parse_shape_pattern(input))
+pub fn parse_shape_pattern(input: &str) -> Result<ShapePattern, ShapePatternError> {
+    let components = parse_syntax(input)?
+        .into_iter()
+        .map(|component| {
+            convert(component).ok_or_else(|| ShapePatternError::InvalidPattern {
+                pattern: input.to_string(),
+                message: "Aliases require `PatternAliases::parse()`".to_string(),
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    ShapePattern::new(components)
 }
 
-/// Parse a `ShapePattern`.
-///
-/// ## Parameters
-///
-/// - `input`: A string representation of the `ShapePattern`
+/// Parse the syntactic components of a pattern.
 ///
 /// ## Errors
 ///
-/// Returns an error if the input string cannot be parsed;
-/// or the pattern is invalid.
-pub fn parse_shape_pattern(input: &str) -> Result<ShapePattern, ShapePatternError> {
-    match components_parser(input.trim()) {
-        Ok((remaining, components)) => {
-            if remaining.is_empty() {
-                Ok(ShapePattern::new(components)?)
-            } else {
-                Err(ShapePatternError::ParseError {
-                    pattern: input.to_string(),
-                })
-            }
-        }
-        Err(_) => Err(ShapePatternError::ParseError {
-            pattern: input.to_string(),
-        }),
-    }
-}
-
-/// Parse an identifier r"[_a-zA-Z][_a-zA-Z0-9]*" -> String
-fn ident_parser(input: &str) -> IResult<&str, String> {
-    map(
-        recognize(pair(
-            alt((alpha1, tag("_"))),
-            many0(alt((alphanumeric1, tag("_")))),
-        )),
-        String::from,
-    )
-    .parse(input)
-}
-
-/// Parse an ellipsis: r"..." -> Ellipsis
-fn ellipsis_parser(input: &str) -> IResult<&str, PatternComponent> {
-    map(tag("..."), |_| PatternComponent::Ellipsis).parse(input)
-}
-
-/// Parse a dimension: identifier -> Dim
-fn dim_parser(input: &str) -> IResult<&str, PatternComponent> {
-    map(ident_parser, PatternComponent::Dim).parse(input)
-}
-
-/// Parse a composite dimension: (id1 id2 ...) -> Composite
-fn composite_parser(input: &str) -> IResult<&str, PatternComponent> {
-    map(
-        delimited(
-            terminated(tag("("), multispace0),
-            separated_list1(multispace1, ident_parser),
-            preceded(multispace0, tag(")")),
-        ),
-        PatternComponent::Composite,
-    )
-    .parse(input)
+/// Returns a `ParseError` if the input string cannot be parsed.
+pub fn parse_syntax(input: &str) -> Result<Vec<Component>, ShapePatternError> {
+    parse_components(input).map_err(|err| ShapePatternError::ParseError {
+        pattern: input.to_string(),
+        span: err.span,
+        expected: err.expected.iter().map(ToString::to_string).collect(),
+    })
 }
 
-/// Parse a list of components separated by whitespace
-fn components_parser(input: &str) -> IResult<&str, Vec<PatternComponent>> {
-    many1(terminated(
-        alt((ellipsis_parser, dim_parser, composite_parser)),
-        multispace0,
-    ))
-    .parse(input)
+/// Convert a syntactic component; `None` for an unexpanded alias.
+pub fn convert(component: Component) -> Option<PatternComponent> {
+    match component {
+        Component::Dim(id) => Some(PatternComponent::Dim(id)),
+        Component::Ellipsis => Some(PatternComponent::Ellipsis),
+        Component::Composite(ids) => Some(PatternComponent::Composite(ids)),
+        Component::Literal(size) => Some(PatternComponent::Literal(size)),
+        Component::Alias(_) => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_ellipsis() {
-        assert_eq!(ellipsis_parser("..."), Ok(("", PatternComponent::Ellipsis)));
-        assert_eq!(
-            ellipsis_parser("... "),
-            Ok((" ", PatternComponent::Ellipsis))
-        );
-        assert_eq!(
-            ellipsis_parser("...x"),
-            Ok(("x", PatternComponent::Ellipsis))
-        );
-    }
-
-    #[test]
-    fn test_identifier() {
-        for prefix in &["_", "a", "A"] {
-            for suffix in &["", "_", "a", "A", "1", "_"] {
-                let id = format!("{prefix}{suffix}");
-                let input = format!("{id} z");
-                assert_eq!(ident_parser(&input), Ok((" z", id)));
-            }
-        }
-
-        // TODO: bad inputs
-    }
-
-    #[test]
-    fn test_dimension() {
-        for id in &["x", "X", "_", "x1", "X1", "_1"] {
-            let input = format!("{id} z");
-            assert_eq!(
-                dim_parser(&input),
-                Ok((" z", PatternComponent::Dim((*id).to_string())))
-            );
-        }
-    }
-
-    #[test]
-    fn test_composite() {
-        assert_eq!(
-            composite_parser("(x)"),
-            Ok(("", PatternComponent::Composite(vec!["x".to_string()])))
-        );
-        assert_eq!(
-            composite_parser("(x y)"),
-            Ok((
-                "",
-                PatternComponent::Composite(vec!["x".to_string(), "y".to_string()])
-            ))
-        );
-    }
-
     #[test]
     fn test_parse_shape_pattern() {
         assert_eq!(
@@ -180,5 +74,9 @@ mod tests {
                 PatternComponent::Dim("c".to_string())
             ])
         );
+        assert!(matches!(
+            parse_shape_pattern("b $img"),
+            Err(ShapePatternError::InvalidPattern { .. })
+        ));
     }
 }
@@ -0,0 +1,183 @@
+use crate::shapes::exp::{ShapePattern, ShapePatternError};
+use crate::shapes::lazy::LazyShapePattern;
+use std::ops::Deref;
+
+/// A reference to a parsed `ShapePattern`, with any rank constraint known at compile time.
+///
+/// `TensorWrapper::unpacks_pattern()` checks `MIN_RANK` and `MAX_RANK` against
+/// the tensor rank `D` at compile time.
+pub trait PatternHandle {
+    /// The minimum tensor rank the pattern can match.
+    const MIN_RANK: usize;
+
+    /// The maximum tensor rank the pattern can match; `None` if unbounded or unknown.
+    const MAX_RANK: Option<usize>;
+
+    /// Get the pattern.
+    fn shape_pattern(&self) -> &ShapePattern;
+}
+
+/// Check, in a `const` context, that a pattern handle can match a tensor of rank `D`.
+#[must_use]
+pub const fn accepts_rank<P: PatternHandle + ?Sized>(rank: usize) -> bool {
+    rank >= P::MIN_RANK
+        && match P::MAX_RANK {
+            Some(max) => rank <= max,
+            None => true,
+        }
+}
+
+impl PatternHandle for ShapePattern {
+    const MIN_RANK: usize = 0;
+    const MAX_RANK: Option<usize> = None;
+
+    fn shape_pattern(&self) -> &ShapePattern {
+        self
+    }
+}
+
+impl PatternHandle for LazyShapePattern {
+    const MIN_RANK: usize = 0;
+    const MAX_RANK: Option<usize> = None;
+
+    fn shape_pattern(&self) -> &ShapePattern {
+        self.get()
+    }
+}
+
+/// A `ShapePattern` which carries its arity in its type.
+///
+/// - `ARITY`: the number of non-ellipsis components.
+/// - `ELLIPSIS`: whether the pattern has an ellipsis.
+///
+/// Without an ellipsis, the pattern only matches rank `ARITY`;
+/// with one, it matches any rank `>= ARITY`.
+///
+/// Usually built by the `ranked_pattern!` macro, which infers both parameters.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shapes::RankedPattern;
+///
+/// let pattern = RankedPattern::<2, true>::parse("b ... c").unwrap();
+/// assert!(RankedPattern::<3, true>::parse("b ... c").is_err());
+/// assert!(RankedPattern::<2, false>::parse("b ... c").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RankedPattern<const ARITY: usize, const ELLIPSIS: bool> {
+    pattern: ShapePattern,
+}
+
+impl<const ARITY: usize, const ELLIPSIS: bool> RankedPattern<ARITY, ELLIPSIS> {
+    /// Wrap a `ShapePattern`, checking its arity.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the pattern does not have arity `ARITY`,
+    /// or does not agree with `ELLIPSIS`.
+    pub fn new(pattern: ShapePattern) -> Result<Self, ShapePatternError> {
+        let ellipsis = pattern.has_ellipsis();
+        let arity = pattern.components().len() - usize::from(ellipsis);
+        if arity != ARITY || ellipsis != ELLIPSIS {
+            return Err(ShapePatternError::InvalidPattern {
+                pattern: pattern.to_string(),
+                message: format!(
+                    "Expected arity {ARITY} (ellipsis: {ELLIPSIS}), found arity {arity} (ellipsis: {ellipsis})"
+                ),
+            });
+        }
+        Ok(Self { pattern })
+    }
+
+    /// Parse a `RankedPattern` from a string.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the input string cannot be parsed,
+    /// the pattern is invalid, or it has the wrong arity.
+    pub fn parse(input: &str) -> Result<Self, ShapePatternError> {
+        Self::new(ShapePattern::parse(input)?)
+    }
+
+    /// Wrap a `ShapePattern` whose arity was validated by the `ranked_pattern!` macro.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn from_validated_pattern(pattern: ShapePattern) -> Self {
+        Self { pattern }
+    }
+
+    /// Unwrap the `ShapePattern`.
+    #[must_use]
+    pub fn into_inner(self) -> ShapePattern {
+        self.pattern
+    }
+}
+
+impl<const ARITY: usize, const ELLIPSIS: bool> Deref for RankedPattern<ARITY, ELLIPSIS> {
+    type Target = ShapePattern;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pattern
+    }
+}
+
+impl<const ARITY: usize, const ELLIPSIS: bool> PatternHandle for RankedPattern<ARITY, ELLIPSIS> {
+    const MIN_RANK: usize = ARITY;
+    const MAX_RANK: Option<usize> = if ELLIPSIS { None } else { Some(ARITY) };
+
+    fn shape_pattern(&self) -> &ShapePattern {
+        &self.pattern
+    }
+}
+
+impl<P: PatternHandle + ?Sized> PatternHandle for &P {
+    const MIN_RANK: usize = P::MIN_RANK;
+    const MAX_RANK: Option<usize> = P::MAX_RANK;
+
+    fn shape_pattern(&self) -> &ShapePattern {
+        (**self).shape_pattern()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_rank() {
+        assert!(accepts_rank::<ShapePattern>(0));
+        assert!(accepts_rank::<ShapePattern>(7));
+
+        assert!(!accepts_rank::<RankedPattern<3, false>>(2));
+        assert!(accepts_rank::<RankedPattern<3, false>>(3));
+        assert!(!accepts_rank::<RankedPattern<3, false>>(4));
+
+        assert!(!accepts_rank::<RankedPattern<3, true>>(2));
+        assert!(accepts_rank::<RankedPattern<3, true>>(3));
+        assert!(accepts_rank::<RankedPattern<3, true>>(4));
+
+        assert!(accepts_rank::<&RankedPattern<3, true>>(4));
+    }
+
+    #[test]
+    fn test_ranked_pattern() {
+        let pattern = RankedPattern::<3, false>::parse("b (h p) c").unwrap();
+        assert_eq!(pattern.to_string(), "b (h p) c");
+        assert_eq!(
+            pattern.into_inner(),
+            ShapePattern::parse("b (h p) c").unwrap()
+        );
+
+        assert!(matches!(
+            RankedPattern::<2, false>::parse("b (h p) c"),
+            Err(ShapePatternError::InvalidPattern { .. })
+        ));
+    }
+
+    #[test]
+    fn test_ranked_pattern_macro() {
+        let pattern: RankedPattern<2, true> = crate::ranked_pattern!("b ... c");
+        assert_eq!(pattern, RankedPattern::parse("b ... c").unwrap());
+    }
+}
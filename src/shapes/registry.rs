@@ -0,0 +1,671 @@
+//! Named shape contracts, with default bindings.
+//!
+//! A `ContractRegistry` can be built in code, or loaded from a TOML or JSON
+//! contract file (with the `toml` or `json` feature):
+//!
+//! ```toml
+//! d = 512
+//!
+//! [encoder]
+//! input = "b t d"
+//! output = "b t d"
+//!
+//! [decoder]
+//! heads = 8
+//! input = "b t (heads k)"
+//! ```
+//!
+//! String entries are patterns, named by their dotted path (`"encoder.input"`);
+//! integer entries are default bindings, which apply to the patterns in their
+//! table and any nested tables. The nearest binding wins.
+//!
+//! A top-level `[aliases]` table defines `$name` sub-patterns; see `PatternAliases`:
+//!
+//! ```toml
+//! [aliases]
+//! img = "c h w"
+//!
+//! [vision]
+//! input = "b $img"
+//! ```
+use crate::shapes::alias::PatternAliases;
+use crate::shapes::bindings::ShapeBindingSource;
+use crate::shapes::exp::{ShapeMatch, ShapePattern, ShapePatternError};
+use crate::shapes::suggest::closest_name;
+use burn::prelude::{Backend, Tensor};
+use burn::tensor::BasicOps;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// An error loading or checking a `ContractRegistry`.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum RegistryError {
+    /// A contract file could not be read.
+    #[error("Failed to read contract file \"{path}\": {message}")]
+    Io { path: String, message: String },
+
+    /// A contract file is not well-formed TOML or JSON.
+    #[error("Malformed contract file{}: {message}", in_file(file.as_deref()))]
+    Syntax {
+        file: Option<String>,
+        message: String,
+    },
+
+    /// A contract file entry is not a valid pattern or binding.
+    #[error("Invalid contract entry \"{entry}\"{}: {message}", in_file(file.as_deref()))]
+    Entry {
+        file: Option<String>,
+        entry: String,
+        message: String,
+    },
+
+    /// No contract has the requested name.
+    #[error(
+        "Unknown contract \"{name}\"{}",
+        suggestion.as_ref().map(|s| format!(" (did you mean \"{s}\"?)")).unwrap_or_default()
+    )]
+    UnknownContract {
+        name: String,
+        suggestion: Option<String>,
+    },
+
+    /// A shape does not satisfy a contract.
+    #[error("Contract \"{name}\" violated: {source}")]
+    Violation {
+        name: String,
+        source: Box<ShapePatternError>,
+    },
+}
+
+fn in_file(file: Option<&str>) -> String {
+    file.map(|file| format!(" in \"{file}\""))
+        .unwrap_or_default()
+}
+
+/// A named contract: a pattern, and its default bindings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contract {
+    pattern: ShapePattern,
+    bindings: BTreeMap<String, usize>,
+}
+
+impl Contract {
+    /// Create a new `Contract`.
+    #[must_use]
+    pub fn new(
+        pattern: ShapePattern,
+        bindings: BTreeMap<String, usize>,
+    ) -> Self {
+        Self { pattern, bindings }
+    }
+
+    /// Get the pattern.
+    #[must_use]
+    pub fn pattern(&self) -> &ShapePattern {
+        &self.pattern
+    }
+
+    /// Get the default bindings.
+    #[must_use]
+    pub fn bindings(&self) -> &BTreeMap<String, usize> {
+        &self.bindings
+    }
+}
+
+/// A registry of named shape contracts.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shapes::ShapePattern;
+/// use burn_contracts::shapes::registry::{Contract, ContractRegistry};
+///
+/// let mut registry = ContractRegistry::new();
+/// registry.insert(
+///     "encoder.input",
+///     Contract::new(
+///         ShapePattern::parse("b t d").unwrap(),
+///         [("d".to_string(), 512)].into(),
+///     ),
+/// );
+///
+/// let m = registry
+///     .check_shape("encoder.input", &[2, 10, 512], &[("b", 2)])
+///     .unwrap();
+/// assert_eq!(m.bindings["t"], 10);
+/// assert!(registry.check_shape("encoder.input", &[2, 10, 256], &[("b", 2)]).is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContractRegistry {
+    contracts: BTreeMap<String, Contract>,
+    aliases: PatternAliases,
+}
+
+impl ContractRegistry {
+    /// Create an empty `ContractRegistry`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a contract; returning any contract it replaces.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        contract: Contract,
+    ) -> Option<Contract> {
+        self.contracts.insert(name.into(), contract)
+    }
+
+    /// Get the pattern aliases; used to parse contract file patterns.
+    #[must_use]
+    pub fn aliases(&self) -> &PatternAliases {
+        &self.aliases
+    }
+
+    /// Get the pattern aliases, mutably.
+    pub fn aliases_mut(&mut self) -> &mut PatternAliases {
+        &mut self.aliases
+    }
+
+    /// Get a contract by name.
+    #[must_use]
+    pub fn get(
+        &self,
+        name: &str,
+    ) -> Option<&Contract> {
+        self.contracts.get(name)
+    }
+
+    /// Iterate over the contract names, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.contracts.keys().map(String::as_str)
+    }
+
+    /// The number of contracts.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.contracts.len()
+    }
+
+    /// Check if there are no contracts.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.contracts.is_empty()
+    }
+
+    /// Get a contract by name, or an `UnknownContract` error.
+    fn lookup(
+        &self,
+        name: &str,
+    ) -> Result<&Contract, RegistryError> {
+        self.get(name)
+            .ok_or_else(|| RegistryError::UnknownContract {
+                name: name.to_string(),
+                suggestion: closest_name(name, self.names()).map(str::to_string),
+            })
+    }
+
+    /// Check a shape against a named contract.
+    ///
+    /// ## Parameters
+    ///
+    /// - `name`: The contract name.
+    /// - `shape`: The shape to check.
+    /// - `bindings`: Extra bindings; these take precedence over the contract defaults.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `UnknownContract` if there is no such contract;
+    /// or `Violation` if the shape does not match.
+    pub fn check_shape<C: ShapeBindingSource>(
+        &self,
+        name: &str,
+        shape: &[usize],
+        bindings: C,
+    ) -> Result<ShapeMatch, RegistryError> {
+        let contract = self.lookup(name)?;
+        contract
+            .pattern
+            .match_bindings(shape, (bindings, &contract.bindings))
+            .map_err(|source| RegistryError::Violation {
+                name: name.to_string(),
+                source: Box::new(source),
+            })
+    }
+
+    /// Check a tensor against a named contract, with only the contract's default bindings.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `UnknownContract` if there is no such contract;
+    /// or `Violation` if the tensor shape does not match.
+    pub fn check<B, const D: usize, K>(
+        &self,
+        name: &str,
+        tensor: &Tensor<B, D, K>,
+    ) -> Result<ShapeMatch, RegistryError>
+    where
+        B: Backend,
+        K: BasicOps<B>,
+    {
+        self.check_with(name, tensor, &[] as &[(&str, usize)])
+    }
+
+    /// Check a tensor against a named contract, with extra bindings.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `UnknownContract` if there is no such contract;
+    /// or `Violation` if the tensor shape does not match.
+    pub fn check_with<B, const D: usize, K, C>(
+        &self,
+        name: &str,
+        tensor: &Tensor<B, D, K>,
+        bindings: C,
+    ) -> Result<ShapeMatch, RegistryError>
+    where
+        B: Backend,
+        K: BasicOps<B>,
+        C: ShapeBindingSource,
+    {
+        self.check_shape(name, &tensor.dims(), bindings)
+    }
+
+    /// Load a contract file, choosing the format by its `.toml` or `.json` extension.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the file cannot be read, its format is not enabled,
+    /// or it is malformed; entry errors name the offending entry and file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        let path = path.as_ref();
+        let file = path.display().to_string();
+        let io_error = |message: String| RegistryError::Io {
+            path: file.clone(),
+            message,
+        };
+
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let parse: Option<Loader> = match extension {
+            #[cfg(feature = "toml")]
+            "toml" => Some(loader::from_toml),
+            #[cfg(feature = "json")]
+            "json" => Some(loader::from_json),
+            _ => None,
+        };
+        let Some(parse) = parse else {
+            return Err(io_error(format!(
+                "unsupported contract file extension \"{extension}\""
+            )));
+        };
+
+        let source = std::fs::read_to_string(path).map_err(|err| io_error(err.to_string()))?;
+        parse(&source, Some(&file))
+    }
+
+    /// Load contracts from a TOML string.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the source is malformed; entry errors name the offending entry.
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(source: &str) -> Result<Self, RegistryError> {
+        loader::from_toml(source, None)
+    }
+
+    /// Load contracts from a JSON string.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the source is malformed; entry errors name the offending entry.
+    #[cfg(feature = "json")]
+    pub fn from_json_str(source: &str) -> Result<Self, RegistryError> {
+        loader::from_json(source, None)
+    }
+}
+
+/// Parse a contract file's source; naming the file, if any, in errors.
+type Loader = fn(&str, Option<&str>) -> Result<ContractRegistry, RegistryError>;
+
+#[cfg(any(feature = "toml", feature = "json"))]
+mod loader {
+    use super::{Contract, ContractRegistry, RegistryError};
+    use serde::Deserialize;
+    use serde::de::IgnoredAny;
+    use std::collections::BTreeMap;
+
+    /// A contract file entry; the shared tree of TOML and JSON files.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub(super) enum Node {
+        Pattern(String),
+        Size(u64),
+        Negative(i64),
+        Table(BTreeMap<String, Node>),
+        Other(IgnoredAny),
+    }
+
+    #[cfg(feature = "toml")]
+    pub(super) fn from_toml(
+        source: &str,
+        file: Option<&str>,
+    ) -> Result<ContractRegistry, RegistryError> {
+        let root: BTreeMap<String, Node> =
+            toml::from_str(source).map_err(|err| RegistryError::Syntax {
+                file: file.map(str::to_string),
+                // The full message; with the line, column and source excerpt.
+                message: err.to_string(),
+            })?;
+        from_tree(root, file)
+    }
+
+    #[cfg(feature = "json")]
+    pub(super) fn from_json(
+        source: &str,
+        file: Option<&str>,
+    ) -> Result<ContractRegistry, RegistryError> {
+        let root: BTreeMap<String, Node> =
+            serde_json::from_str(source).map_err(|err| RegistryError::Syntax {
+                file: file.map(str::to_string),
+                message: err.to_string(),
+            })?;
+        from_tree(root, file)
+    }
+
+    pub(super) fn from_tree(
+        mut root: BTreeMap<String, Node>,
+        file: Option<&str>,
+    ) -> Result<ContractRegistry, RegistryError> {
+        let mut registry = ContractRegistry::new();
+
+        if let Some(aliases) = root.remove(ALIASES) {
+            load_aliases(&mut registry, &aliases, file)?;
+        }
+
+        load_table(&mut registry, &root, "", &BTreeMap::new(), file)?;
+        Ok(registry)
+    }
+
+    /// The reserved top-level table of pattern aliases.
+    const ALIASES: &str = "aliases";
+
+    fn load_aliases(
+        registry: &mut ContractRegistry,
+        aliases: &Node,
+        file: Option<&str>,
+    ) -> Result<(), RegistryError> {
+        let entry_error = |entry: String, message: String| RegistryError::Entry {
+            file: file.map(str::to_string),
+            entry,
+            message,
+        };
+
+        let Node::Table(table) = aliases else {
+            return Err(entry_error(
+                ALIASES.to_string(),
+                "expected a table of alias patterns".to_string(),
+            ));
+        };
+        for (name, node) in table {
+            let entry = format!("{ALIASES}.{name}");
+            let Node::Pattern(source) = node else {
+                return Err(entry_error(entry, "expected a pattern string".to_string()));
+            };
+            registry
+                .aliases
+                .define(name.clone(), source)
+                .map_err(|err| entry_error(entry, err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn load_table(
+        registry: &mut ContractRegistry,
+        table: &BTreeMap<String, Node>,
+        prefix: &str,
+        inherited: &BTreeMap<String, usize>,
+        file: Option<&str>,
+    ) -> Result<(), RegistryError> {
+        let entry_error = |key: &str, message: String| RegistryError::Entry {
+            file: file.map(str::to_string),
+            entry: format!("{prefix}{key}"),
+            message,
+        };
+
+        // Bindings first; so they apply to every pattern in the table.
+        let mut scope = inherited.clone();
+        for (key, node) in table {
+            match node {
+                Node::Size(size) => {
+                    let size = usize::try_from(*size)
+                        .map_err(|_| entry_error(key, format!("{size} is too large")))?;
+                    scope.insert(key.clone(), size);
+                }
+                Node::Negative(size) => {
+                    return Err(entry_error(
+                        key,
+                        format!("dimension sizes must be non-negative, found {size}"),
+                    ));
+                }
+                Node::Other(_) => {
+                    return Err(entry_error(
+                        key,
+                        "expected a pattern string, a dimension size, or a table".to_string(),
+                    ));
+                }
+                Node::Pattern(_) | Node::Table(_) => {}
+            }
+        }
+
+        for (key, node) in table {
+            match node {
+                Node::Pattern(source) => {
+                    let pattern = registry
+                        .aliases
+                        .parse(source)
+                        .map_err(|err| entry_error(key, err.to_string()))?;
+                    registry.insert(
+                        format!("{prefix}{key}"),
+                        Contract::new(pattern, scope.clone()),
+                    );
+                }
+                Node::Table(nested) => {
+                    load_table(registry, nested, &format!("{prefix}{key}."), &scope, file)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_contract() {
+        let mut registry = ContractRegistry::new();
+        registry.insert(
+            "encoder.input",
+            Contract::new(ShapePattern::parse("b t d").unwrap(), BTreeMap::new()),
+        );
+        assert_eq!(registry.len(), 1);
+
+        let err = registry
+            .check_shape("encoder.inptu", &[1, 2, 3], &[] as &[(&str, usize)])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown contract \"encoder.inptu\" (did you mean \"encoder.input\"?)"
+        );
+    }
+
+    #[test]
+    fn test_check_tensor() {
+        use burn::backend::NdArray;
+
+        let mut registry = ContractRegistry::new();
+        registry.insert(
+            "x",
+            Contract::new(
+                ShapePattern::parse("b (h p)").unwrap(),
+                [("p".to_string(), 4)].into(),
+            ),
+        );
+
+        let device = Default::default();
+        let tensor = Tensor::<NdArray, 2>::zeros([2, 12], &device);
+        assert_eq!(registry.check("x", &tensor).unwrap().bindings["h"], 3);
+
+        // Extra bindings take precedence over the defaults.
+        assert!(matches!(
+            registry.check_with("x", &tensor, &[("p", 5)]),
+            Err(RegistryError::Violation { .. })
+        ));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_from_toml() {
+        let registry = ContractRegistry::from_toml_str(
+            r#"
+            d = 512
+
+            [encoder]
+            input = "b t d"
+
+            [decoder]
+            d = 256
+            heads = 8
+            input = "b t (heads k)"
+            output = "b t d"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            ["decoder.input", "decoder.output", "encoder.input"]
+        );
+        assert_eq!(registry.get("encoder.input").unwrap().bindings()["d"], 512);
+        assert_eq!(registry.get("decoder.output").unwrap().bindings()["d"], 256);
+
+        let no_bindings: &[(&str, usize)] = &[];
+        let m = registry
+            .check_shape("decoder.input", &[2, 3, 64], no_bindings)
+            .unwrap();
+        assert_eq!(m.bindings["k"], 8);
+        assert!(
+            registry
+                .check_shape("encoder.input", &[2, 3, 256], no_bindings)
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_entry_errors() {
+        let err = ContractRegistry::from_toml_str("[encoder]\ninput = \"b (t\"").unwrap_err();
+        assert!(matches!(
+            &err,
+            RegistryError::Entry { entry, .. } if entry == "encoder.input"
+        ));
+        assert!(
+            err.to_string()
+                .starts_with("Invalid contract entry \"encoder.input\": Parse error")
+        );
+
+        assert_eq!(
+            ContractRegistry::from_toml_str("[encoder]\nd = -1").unwrap_err(),
+            RegistryError::Entry {
+                file: None,
+                entry: "encoder.d".to_string(),
+                message: "dimension sizes must be non-negative, found -1".to_string(),
+            }
+        );
+        assert!(matches!(
+            ContractRegistry::from_toml_str("d = 1.5").unwrap_err(),
+            RegistryError::Entry { entry, .. } if entry == "d"
+        ));
+        let err = ContractRegistry::from_toml_str("d = ").unwrap_err();
+        assert!(matches!(err, RegistryError::Syntax { .. }));
+        assert!(err.to_string().contains("line 1, column 5"), "{err}");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_aliases() {
+        let registry = ContractRegistry::from_toml_str(
+            r#"
+            [aliases]
+            img = "c h w"
+            batch = "b $img"
+
+            [vision]
+            c = 3
+            input = "$batch"
+            "#,
+        )
+        .unwrap();
+
+        let pattern = registry.get("vision.input").unwrap().pattern();
+        assert_eq!(pattern.to_string(), "b c h w");
+        assert_eq!(format!("{pattern:#}"), "$batch");
+        assert!(registry.aliases().contains("img"));
+
+        let err =
+            ContractRegistry::from_toml_str("[aliases]\nimg = \"c h w\"\n[x]\ninput = \"$imgs\"")
+                .unwrap_err();
+        assert!(matches!(
+            err,
+            RegistryError::Entry { entry, .. } if entry == "x.input"
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_from_json() {
+        let registry =
+            ContractRegistry::from_json_str(r#"{"d": 512, "encoder.input": "b t d"}"#).unwrap();
+        assert_eq!(registry.get("encoder.input").unwrap().bindings()["d"], 512);
+
+        let err = ContractRegistry::from_json_str(r#"{"encoder": {"input": ["b"]}}"#).unwrap_err();
+        assert!(matches!(
+            err,
+            RegistryError::Entry { entry, .. } if entry == "encoder.input"
+        ));
+    }
+
+    #[cfg(all(feature = "toml", feature = "json"))]
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("burn-contracts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("contracts.toml");
+        std::fs::write(&path, "[encoder]\ninput = \"b (t\"\n").unwrap();
+        let err = ContractRegistry::load(&path).unwrap_err();
+        assert!(matches!(
+            &err,
+            RegistryError::Entry { file: Some(file), entry, .. }
+                if file == &path.display().to_string() && entry == "encoder.input"
+        ));
+
+        let path = dir.join("contracts.json");
+        std::fs::write(&path, r#"{"x": "b c"}"#).unwrap();
+        assert_eq!(ContractRegistry::load(&path).unwrap().len(), 1);
+
+        // The extension is checked before the file is read.
+        let path = dir.join("missing.yaml");
+        let err = ContractRegistry::load(&path).unwrap_err();
+        assert!(matches!(err, RegistryError::Io { .. }));
+        assert!(
+            err.to_string()
+                .ends_with("unsupported contract file extension \"yaml\"")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+//! Serde support for `ShapePattern`; enabled by the `serde` feature.
+//!
+//! A `ShapePattern` serializes as its canonical string, e.g. `"b ... (h p) c"`;
+//! and deserializes from either that string, or a list of `PatternComponent`s.
+//!
+//! Use `#[serde(with = "burn_contracts::shapes::serialization::components")]`
+//! to serialize a field as structured components instead.
+use crate::shapes::exp::{PatternComponent, ShapePattern, ShapePatternError};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The accepted serialized forms of a `ShapePattern`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatternRepr {
+    Source(String),
+    Components(Vec<PatternComponent>),
+}
+
+impl Serialize for ShapePattern {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ShapePattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match PatternRepr::deserialize(deserializer)? {
+            PatternRepr::Source(source) => ShapePattern::parse(&source),
+            PatternRepr::Components(components) => from_components(components),
+        }
+        .map_err(D::Error::custom)
+    }
+}
+
+/// Build a pattern from deserialized components, validating them as the parser would.
+///
+/// The components are rendered and re-parsed; so names must be identifiers,
+/// composites non-empty, and the result must be the same components.
+fn from_components(components: Vec<PatternComponent>) -> Result<ShapePattern, ShapePatternError> {
+    let pattern = ShapePattern::new(components)?;
+    let source = pattern.to_string();
+    let parsed = ShapePattern::parse(&source)?;
+    if parsed != pattern {
+        return Err(ShapePatternError::InvalidPattern {
+            pattern: source,
+            message: format!(
+                "Components {:?} do not round trip through the pattern syntax",
+                pattern.components()
+            ),
+        });
+    }
+    Ok(parsed)
+}
+
+/// Serialize a `ShapePattern` as a list of `PatternComponent`s.
+///
+/// For use with `#[serde(with = "...")]`; deserialization accepts either form.
+pub mod components {
+    use super::{PatternComponent, ShapePattern};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize the pattern as its components.
+    ///
+    /// ## Errors
+    ///
+    /// Returns any error from the serializer.
+    pub fn serialize<S: Serializer>(
+        pattern: &ShapePattern,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        <[PatternComponent]>::serialize(pattern.components(), serializer)
+    }
+
+    /// Deserialize the pattern from either its string or its components.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the input is malformed, or the pattern is invalid.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<ShapePattern, D::Error> {
+        ShapePattern::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pattern_round_trip() {
+        let pattern = ShapePattern::parse("b ...  (h p) c").unwrap();
+        let value = serde_json::to_value(&pattern).unwrap();
+        assert_eq!(value, json!("b ... (h p) c"));
+        assert_eq!(
+            serde_json::from_value::<ShapePattern>(value).unwrap(),
+            pattern
+        );
+
+        let structured = json!(["Ellipsis", {"Dim": "b"}, {"Composite": ["h", "p"]}]);
+        assert_eq!(
+            serde_json::from_value::<ShapePattern>(structured).unwrap(),
+            ShapePattern::parse("... b (h p)").unwrap()
+        );
+
+        let structured = json!([{"Dim": "enc.d"}, {"Literal": 3}, {"Composite": ["h"]}]);
+        assert_eq!(
+            serde_json::from_value::<ShapePattern>(structured).unwrap(),
+            ShapePattern::parse("enc.d 3 (h)").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let err = serde_json::from_value::<ShapePattern>(json!("b (h")).unwrap_err();
+        assert!(err.to_string().starts_with("Parse error for \"b (h\""));
+
+        let err =
+            serde_json::from_value::<ShapePattern>(json!(["Ellipsis", "Ellipsis"])).unwrap_err();
+        assert!(err.to_string().contains("Only one ellipsis is allowed"));
+
+        for components in [
+            json!([{"Dim": "b c"}]),
+            json!([{"Dim": "1b"}]),
+            json!([{"Composite": []}]),
+            json!([{"Composite": ["h", "..."]}]),
+        ] {
+            assert!(
+                serde_json::from_value::<ShapePattern>(components.clone()).is_err(),
+                "{components}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_components_field() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Contract {
+            #[serde(with = "components")]
+            input: ShapePattern,
+        }
+
+        let contract = Contract {
+            input: ShapePattern::parse("b (h p)").unwrap(),
+        };
+        let value = serde_json::to_value(&contract).unwrap();
+        assert_eq!(
+            value,
+            json!({"input": [{"Dim": "b"}, {"Composite": ["h", "p"]}]})
+        );
+        assert_eq!(serde_json::from_value::<Contract>(value).unwrap(), contract);
+        assert_eq!(
+            serde_json::from_value::<Contract>(json!({"input": "b (h p)"})).unwrap(),
+            contract
+        );
+    }
+
+    #[test]
+    fn test_match_and_error() {
+        let pattern = ShapePattern::parse("b (h p)").unwrap();
+        let m = pattern.match_bindings(&[2, 12], &[("p", 4)]).unwrap();
+        let value = serde_json::to_value(&m).unwrap();
+        assert_eq!(value["bindings"], json!({"b": 2, "h": 3, "p": 4}));
+        let m2: crate::shapes::ShapeMatch = serde_json::from_value(value).unwrap();
+        assert_eq!(m2.bindings, m.bindings);
+
+        let err = pattern.match_bindings(&[2, 9], &[("p", 4)]).unwrap_err();
+        let value = serde_json::to_value(&err).unwrap();
+        assert_eq!(value["type"], json!("MatchError"));
+        assert_eq!(value["details"]["kind"], json!("NonDivisibleComposite"));
+        assert_eq!(value["details"]["component"], json!(1));
+        assert_eq!(
+            serde_json::from_value::<ShapePatternError>(value).unwrap(),
+            err
+        );
+    }
+}
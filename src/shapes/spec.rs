@@ -0,0 +1,322 @@
+//! Combined element type and shape specs, such as `"Float[b c h w]"`.
+use crate::shapes::exp::{ShapePattern, ShapePatternError};
+use crate::shapes::suggest::closest_name;
+use burn::tensor::DType;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The element type constraint of a `TensorSpec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ElementSpec {
+    /// Any tensor kind and dtype.
+    Shaped,
+
+    /// A `Float` tensor, of any float dtype.
+    Float,
+
+    /// An `Int` tensor, of any int dtype.
+    Int,
+
+    /// A `Bool` tensor.
+    Bool,
+
+    /// A tensor of exactly this dtype.
+    DType(ExactDType),
+}
+
+/// The exact dtypes a `TensorSpec` can name; each is a Burn `DType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExactDType {
+    F16,
+    BF16,
+    /// Also accepts `DType::Flex32`.
+    F32,
+    F64,
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl ExactDType {
+    /// The Burn `DType`.
+    #[must_use]
+    pub fn dtype(self) -> DType {
+        match self {
+            ExactDType::F16 => DType::F16,
+            ExactDType::BF16 => DType::BF16,
+            ExactDType::F32 => DType::F32,
+            ExactDType::F64 => DType::F64,
+            ExactDType::I8 => DType::I8,
+            ExactDType::I16 => DType::I16,
+            ExactDType::I32 => DType::I32,
+            ExactDType::I64 => DType::I64,
+            ExactDType::U8 => DType::U8,
+            ExactDType::U16 => DType::U16,
+            ExactDType::U32 => DType::U32,
+            ExactDType::U64 => DType::U64,
+        }
+    }
+
+    /// The spec name, e.g. `Float32`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            ExactDType::F16 => "Float16",
+            ExactDType::BF16 => "BFloat16",
+            ExactDType::F32 => "Float32",
+            ExactDType::F64 => "Float64",
+            ExactDType::I8 => "Int8",
+            ExactDType::I16 => "Int16",
+            ExactDType::I32 => "Int32",
+            ExactDType::I64 => "Int64",
+            ExactDType::U8 => "UInt8",
+            ExactDType::U16 => "UInt16",
+            ExactDType::U32 => "UInt32",
+            ExactDType::U64 => "UInt64",
+        }
+    }
+
+    /// Check a tensor's dtype.
+    #[must_use]
+    pub fn accepts(
+        self,
+        dtype: DType,
+    ) -> bool {
+        match self {
+            ExactDType::F32 => matches!(dtype, DType::F32 | DType::Flex32),
+            exact => dtype == exact.dtype(),
+        }
+    }
+}
+
+impl From<ExactDType> for DType {
+    fn from(exact: ExactDType) -> Self {
+        exact.dtype()
+    }
+}
+
+/// The spec names, with their element types; for parsing.
+const ELEMENT_NAMES: &[(&str, ElementSpec)] = &[
+    ("Shaped", ElementSpec::Shaped),
+    ("Float", ElementSpec::Float),
+    ("Int", ElementSpec::Int),
+    ("Bool", ElementSpec::Bool),
+    ("Float16", ElementSpec::DType(ExactDType::F16)),
+    ("BFloat16", ElementSpec::DType(ExactDType::BF16)),
+    ("Float32", ElementSpec::DType(ExactDType::F32)),
+    ("Float64", ElementSpec::DType(ExactDType::F64)),
+    ("Int8", ElementSpec::DType(ExactDType::I8)),
+    ("Int16", ElementSpec::DType(ExactDType::I16)),
+    ("Int32", ElementSpec::DType(ExactDType::I32)),
+    ("Int64", ElementSpec::DType(ExactDType::I64)),
+    ("UInt8", ElementSpec::DType(ExactDType::U8)),
+    ("UInt16", ElementSpec::DType(ExactDType::U16)),
+    ("UInt32", ElementSpec::DType(ExactDType::U32)),
+    ("UInt64", ElementSpec::DType(ExactDType::U64)),
+];
+
+impl ElementSpec {
+    /// The spec name, e.g. `Float` or `Int32`.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            ElementSpec::Shaped => "Shaped",
+            ElementSpec::Float => "Float",
+            ElementSpec::Int => "Int",
+            ElementSpec::Bool => "Bool",
+            ElementSpec::DType(exact) => exact.name(),
+        }
+    }
+
+    /// Check a tensor's Burn kind (`"Float"`, `"Int"` or `"Bool"`) and dtype.
+    #[must_use]
+    pub fn accepts(
+        &self,
+        kind: &str,
+        dtype: DType,
+    ) -> bool {
+        match self {
+            ElementSpec::Shaped => true,
+            ElementSpec::Float => kind == "Float",
+            ElementSpec::Int => kind == "Int",
+            ElementSpec::Bool => kind == "Bool",
+            ElementSpec::DType(exact) => exact.accepts(dtype),
+        }
+    }
+}
+
+impl Display for ElementSpec {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A jaxtyping-style tensor spec: an element type and a `ShapePattern`,
+/// written `"Float[b c h w]"`, `"Int[b t]"` or `"Bool[b 1 t t]"`.
+///
+/// The element types are `Shaped` (any), the Burn kinds `Float`, `Int` and `Bool`,
+/// and exact dtypes: `Float16`, `BFloat16`, `Float32`, `Float64`, `Int8` to `Int64`,
+/// and `UInt8` to `UInt64`.
+///
+/// See `TensorWrapper::conforms()`.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shapes::{ElementSpec, TensorSpec};
+///
+/// let spec = TensorSpec::parse("Bool[b 1 t t]").unwrap();
+/// assert_eq!(spec.element, ElementSpec::Bool);
+/// assert_eq!(spec.pattern.to_string(), "b 1 t t");
+/// assert_eq!(spec.to_string(), "Bool[b 1 t t]");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TensorSpec {
+    /// The element type constraint.
+    pub element: ElementSpec,
+
+    /// The shape pattern.
+    pub pattern: ShapePattern,
+}
+
+impl TensorSpec {
+    /// Parse a `TensorSpec`, such as `"Float[b c h w]"`.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the input is not of the form `Element[pattern]`,
+    /// the element type is unknown, or the pattern cannot be parsed.
+    pub fn parse(input: &str) -> Result<Self, ShapePatternError> {
+        let invalid = |message: String| ShapePatternError::InvalidPattern {
+            pattern: input.to_string(),
+            message,
+        };
+
+        let trimmed = input.trim_end();
+        let (Some(open), true) = (trimmed.find('['), trimmed.ends_with(']')) else {
+            return Err(invalid(
+                "Expected a tensor spec, such as \"Float[b c h w]\"".to_string(),
+            ));
+        };
+
+        let name = trimmed[..open].trim();
+        let Some(element) = ELEMENT_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, element)| *element)
+        else {
+            let suggestion = closest_name(name, ELEMENT_NAMES.iter().map(|(n, _)| *n))
+                .map(|s| format!(" (did you mean \"{s}\"?)"))
+                .unwrap_or_default();
+            return Err(invalid(format!(
+                "Unknown element type \"{name}\"{suggestion}"
+            )));
+        };
+
+        let offset = open + 1;
+        let body = &trimmed[offset..trimmed.len() - 1];
+        let pattern = if body.trim().is_empty() {
+            ShapePattern::new(Vec::new())?
+        } else {
+            match ShapePattern::parse(body) {
+                Ok(pattern) => pattern,
+                // Report syntax errors against the whole spec.
+                Err(ShapePatternError::ParseError { span, expected, .. }) => {
+                    return Err(ShapePatternError::ParseError {
+                        pattern: input.to_string(),
+                        span: span.start + offset..span.end + offset,
+                        expected,
+                    });
+                }
+                Err(ShapePatternError::InvalidPattern { message, .. }) => {
+                    return Err(invalid(message));
+                }
+                Err(err) => return Err(err),
+            }
+        };
+
+        Ok(Self { element, pattern })
+    }
+}
+
+impl FromStr for TensorSpec {
+    type Err = ShapePatternError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::parse(input)
+    }
+}
+
+impl Display for TensorSpec {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{}[{}]", self.element, self.pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for input in [
+            "Float[b c h w]",
+            "Int[b t]",
+            "Bool[b 1 t t]",
+            "Float32[b ... (h p)]",
+            "Shaped[]",
+        ] {
+            assert_eq!(TensorSpec::parse(input).unwrap().to_string(), input);
+        }
+
+        let spec: TensorSpec = " UInt8 [ b c ] ".parse().unwrap();
+        assert_eq!(spec.element, ElementSpec::DType(ExactDType::U8));
+        assert_eq!(spec.to_string(), "UInt8[b c]");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            TensorSpec::parse("Flaot[b c]").unwrap_err().to_string(),
+            "Invalid pattern \"Flaot[b c]\": Unknown element type \"Flaot\" (did you mean \"Float\"?)"
+        );
+        assert!(TensorSpec::parse("b c").is_err());
+        assert!(TensorSpec::parse("Float[b c").is_err());
+
+        let ShapePatternError::ParseError { span, .. } =
+            TensorSpec::parse("Float[b (h w]").unwrap_err()
+        else {
+            panic!("expected a ParseError");
+        };
+        assert_eq!(span, 12..12);
+    }
+
+    #[test]
+    fn test_names() {
+        for (name, element) in ELEMENT_NAMES {
+            assert_eq!(element.name(), *name);
+        }
+        assert_eq!(ElementSpec::DType(ExactDType::BF16).to_string(), "BFloat16");
+        assert_eq!(DType::from(ExactDType::U16), DType::U16);
+    }
+
+    #[test]
+    fn test_accepts() {
+        assert!(ElementSpec::Shaped.accepts("Int", DType::I64));
+        assert!(ElementSpec::Float.accepts("Float", DType::F16));
+        assert!(!ElementSpec::Float.accepts("Int", DType::I64));
+        assert!(ElementSpec::DType(ExactDType::F32).accepts("Float", DType::Flex32));
+        assert!(!ElementSpec::DType(ExactDType::F32).accepts("Float", DType::F64));
+    }
+}
@@ -0,0 +1,63 @@
+//! "Did you mean" suggestions for misspelled dimension names.
+
+/// The Levenshtein edit distance between two strings, in chars.
+pub(crate) fn edit_distance(
+    a: &str,
+    b: &str,
+) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let next = (diag + usize::from(ca != *cb))
+                .min(row[j] + 1)
+                .min(row[j + 1] + 1);
+            diag = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// Find the candidate closest to `name`, if any is close enough to be a likely typo.
+///
+/// A candidate is close enough if it is within an edit distance of a third of the
+/// length of `name`, rounded up; ties go to the earliest candidate.
+pub(crate) fn closest_name<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let limit = name.chars().count().div_ceil(3);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("chanels", "channels"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("höhe", "hohe"), 1);
+    }
+
+    #[test]
+    fn test_closest_name() {
+        let names = ["batch", "channels", "height", "width"];
+        assert_eq!(closest_name("chanels", names), Some("channels"));
+        assert_eq!(closest_name("widht", names), Some("width"));
+        assert_eq!(closest_name("h", names), None);
+        assert_eq!(closest_name("depth", names), None);
+    }
+}
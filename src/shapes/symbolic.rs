@@ -0,0 +1,430 @@
+//! Dimension size types, for matching patterns against symbolic shapes.
+//!
+//! `ShapePattern::match_dims()` matches any `DimSize`; `usize` for concrete shapes,
+//! and `SymDim` for symbolic shapes such as `["batch", "3", "seq*2"]`, where a
+//! constraint which cannot be decided becomes a `Residual` equation.
+use crate::shapes::exp::ShapePatternError;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Display};
+use std::ops::Range;
+use std::str::FromStr;
+
+/// The result of solving `factor * divisor == dim` for `factor`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Solved<D> {
+    /// The unique factor.
+    Factor(D),
+
+    /// Any factor is a solution; `0 * divisor == 0`.
+    Indeterminate,
+
+    /// There is no solution.
+    Impossible,
+
+    /// Solutions depend on unknown sizes.
+    Undecided,
+}
+
+/// A dimension size which patterns can be matched against.
+pub trait DimSize: Clone + PartialEq + Debug + Display {
+    /// A fixed size.
+    fn from_usize(size: usize) -> Self;
+
+    /// The concrete size, if known.
+    fn to_usize(&self) -> Option<usize>;
+
+    /// The product of two sizes; `None` on overflow.
+    fn checked_mul(
+        &self,
+        other: &Self,
+    ) -> Option<Self>;
+
+    /// Whether two sizes are equal; `None` if it depends on unknown sizes.
+    fn decide_eq(
+        &self,
+        other: &Self,
+    ) -> Option<bool>;
+
+    /// Solve `factor * divisor == self` for `factor`.
+    fn solve_factor(
+        &self,
+        divisor: &Self,
+    ) -> Solved<Self>;
+
+    /// An unknown size, standing for the pattern dimension `name`;
+    /// `None` if the type has no unknowns.
+    fn unknown(name: &str) -> Option<Self>;
+}
+
+impl DimSize for usize {
+    fn from_usize(size: usize) -> Self {
+        size
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        Some(*self)
+    }
+
+    fn checked_mul(
+        &self,
+        other: &Self,
+    ) -> Option<Self> {
+        usize::checked_mul(*self, *other)
+    }
+
+    fn decide_eq(
+        &self,
+        other: &Self,
+    ) -> Option<bool> {
+        Some(self == other)
+    }
+
+    fn solve_factor(
+        &self,
+        divisor: &Self,
+    ) -> Solved<Self> {
+        match self.checked_rem(*divisor) {
+            Some(0) => Solved::Factor(self / divisor),
+            None if *self == 0 => Solved::Indeterminate,
+            _ => Solved::Impossible,
+        }
+    }
+
+    fn unknown(_name: &str) -> Option<Self> {
+        None
+    }
+}
+
+/// A symbolic dimension size: a product of a constant and named sizes, e.g. `seq*2`.
+///
+/// Parsed from `*`-separated sizes and names, which may be dotted like pattern names;
+/// and `name^k` is a power;
+/// displayed in a canonical form, with names sorted and the constant last.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::shapes::SymDim;
+///
+/// let dim: SymDim = "2 * seq".parse().unwrap();
+/// assert_eq!(dim.to_string(), "seq*2");
+/// assert_eq!(dim, SymDim::symbol("seq") * SymDim::from(2));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SymDim {
+    coeff: usize,
+    symbols: BTreeMap<String, u32>,
+}
+
+impl SymDim {
+    /// A named size.
+    #[must_use]
+    pub fn symbol(name: impl Into<String>) -> Self {
+        Self {
+            coeff: 1,
+            symbols: [(name.into(), 1)].into(),
+        }
+    }
+
+    /// The constant factor.
+    #[must_use]
+    pub fn coeff(&self) -> usize {
+        self.coeff
+    }
+
+    /// The named factors, with their powers.
+    #[must_use]
+    pub fn symbols(&self) -> &BTreeMap<String, u32> {
+        &self.symbols
+    }
+
+    /// Evaluate the size, given values for its names.
+    ///
+    /// Returns `None` if a name has no value, or the product overflows.
+    pub fn eval(
+        &self,
+        values: &HashMap<String, usize>,
+    ) -> Option<usize> {
+        self.symbols
+            .iter()
+            .try_fold(self.coeff, |acc, (name, pow)| {
+                acc.checked_mul(values.get(name)?.checked_pow(*pow)?)
+            })
+    }
+
+    fn is_const(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Zero absorbs all names.
+    fn normalized(mut self) -> Self {
+        if self.coeff == 0 {
+            self.symbols.clear();
+        }
+        self
+    }
+}
+
+impl From<usize> for SymDim {
+    fn from(size: usize) -> Self {
+        Self {
+            coeff: size,
+            symbols: BTreeMap::new(),
+        }
+        .normalized()
+    }
+}
+
+impl std::ops::Mul for SymDim {
+    type Output = SymDim;
+
+    /// ## Panics
+    ///
+    /// Panics if the constant factor overflows.
+    fn mul(
+        self,
+        rhs: Self,
+    ) -> Self::Output {
+        DimSize::checked_mul(&self, &rhs).expect("SymDim constant overflow")
+    }
+}
+
+impl FromStr for SymDim {
+    type Err = ShapePatternError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || ShapePatternError::InvalidPattern {
+            pattern: input.to_string(),
+            message: "Expected a symbolic dimension; `*`-separated sizes and names".to_string(),
+        };
+        let is_name = |s: &str| {
+            s.split('.').all(|segment| {
+                segment
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_alphabetic() || c == '_')
+                    && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+            })
+        };
+
+        let mut dim = SymDim::from(1);
+        for factor in input.split('*').map(str::trim) {
+            let (base, pow) = match factor.split_once('^') {
+                Some((base, pow)) => (base.trim(), pow.trim().parse().map_err(|_| invalid())?),
+                None => (factor, 1),
+            };
+            let factor = if let Ok(size) = base.parse::<usize>() {
+                SymDim::from(size.checked_pow(pow).ok_or_else(invalid)?)
+            } else if is_name(base) && pow > 0 {
+                SymDim {
+                    coeff: 1,
+                    symbols: [(base.to_string(), pow)].into(),
+                }
+            } else {
+                return Err(invalid());
+            };
+            dim = DimSize::checked_mul(&dim, &factor).ok_or_else(invalid)?;
+        }
+        Ok(dim)
+    }
+}
+
+impl Display for SymDim {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let mut factors: Vec<String> = self
+            .symbols
+            .iter()
+            .map(|(name, pow)| match pow {
+                1 => name.clone(),
+                pow => format!("{name}^{pow}"),
+            })
+            .collect();
+        if self.coeff != 1 || factors.is_empty() {
+            factors.push(self.coeff.to_string());
+        }
+        write!(f, "{}", factors.join("*"))
+    }
+}
+
+impl DimSize for SymDim {
+    fn from_usize(size: usize) -> Self {
+        SymDim::from(size)
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        self.is_const().then_some(self.coeff)
+    }
+
+    fn checked_mul(
+        &self,
+        other: &Self,
+    ) -> Option<Self> {
+        let mut product = SymDim {
+            coeff: self.coeff.checked_mul(other.coeff)?,
+            symbols: self.symbols.clone(),
+        };
+        for (name, pow) in &other.symbols {
+            let entry = product.symbols.entry(name.clone()).or_insert(0);
+            *entry = entry.checked_add(*pow)?;
+        }
+        Some(product.normalized())
+    }
+
+    fn decide_eq(
+        &self,
+        other: &Self,
+    ) -> Option<bool> {
+        if self == other {
+            Some(true)
+        } else if self.is_const() && other.is_const() {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn solve_factor(
+        &self,
+        divisor: &Self,
+    ) -> Solved<Self> {
+        if divisor.coeff == 0 {
+            return match self.to_usize() {
+                Some(0) => Solved::Indeterminate,
+                Some(_) => Solved::Impossible,
+                None => Solved::Undecided,
+            };
+        }
+
+        // Exact monomial division.
+        let mut quotient = SymDim {
+            coeff: self.coeff / divisor.coeff,
+            symbols: self.symbols.clone(),
+        };
+        let mut exact = self.coeff.is_multiple_of(divisor.coeff);
+        for (name, pow) in &divisor.symbols {
+            match quotient.symbols.get_mut(name) {
+                Some(have) if *have > *pow => *have -= pow,
+                Some(have) if *have == *pow => {
+                    quotient.symbols.remove(name);
+                }
+                _ => exact = false,
+            }
+        }
+
+        if exact {
+            Solved::Factor(quotient)
+        } else if self.is_const() && divisor.is_const() {
+            Solved::Impossible
+        } else {
+            Solved::Undecided
+        }
+    }
+
+    fn unknown(name: &str) -> Option<Self> {
+        Some(SymDim::symbol(name))
+    }
+}
+
+/// An equation between sizes which matching could not decide;
+/// the match holds for the sizes which satisfy it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Residual<D> {
+    /// The index of the pattern component which produced the equation.
+    pub component: usize,
+
+    /// The size the pattern requires.
+    pub expected: D,
+
+    /// The size in the shape.
+    pub actual: D,
+}
+
+impl<D: Display> Display for Residual<D> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{} == {}", self.expected, self.actual)
+    }
+}
+
+/// The result of `ShapePattern::match_dims()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DimMatch<D> {
+    /// The matched shape.
+    pub shape: Vec<D>,
+
+    /// The sizes of the pattern dimensions.
+    pub bindings: HashMap<String, D>,
+
+    /// The equations which must also hold; always empty for concrete sizes.
+    pub residuals: Vec<Residual<D>>,
+
+    /// The shape axes covered by the ellipsis, if any.
+    pub ellipsis_range: Option<Range<usize>>,
+}
+
+impl<D: Clone> DimMatch<D> {
+    /// Select a subset of the bindings.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a key is not found in the bindings.
+    #[must_use]
+    pub fn select<const N: usize>(
+        &self,
+        keys: [&str; N],
+    ) -> [D; N] {
+        keys.map(|key| self.bindings[key].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sym(input: &str) -> SymDim {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn test_sym_dim_parse() {
+        assert_eq!(sym("seq*2").to_string(), "seq*2");
+        assert_eq!(sym("2 * seq * b").to_string(), "b*seq*2");
+        assert_eq!(sym("h^2*h").to_string(), "h^3");
+        assert_eq!(sym("4*3").to_string(), "12");
+        assert_eq!(sym("0*seq").to_string(), "0");
+        assert_eq!(sym("1").to_string(), "1");
+
+        assert!("".parse::<SymDim>().is_err());
+        assert!("a+b".parse::<SymDim>().is_err());
+        assert!("2seq".parse::<SymDim>().is_err());
+        assert!("a^0".parse::<SymDim>().is_err());
+    }
+
+    #[test]
+    fn test_sym_dim_arithmetic() {
+        assert_eq!(
+            sym("seq*2").solve_factor(&sym("2")),
+            Solved::Factor(sym("seq"))
+        );
+        assert_eq!(
+            sym("h^2*8").solve_factor(&sym("h*4")),
+            Solved::Factor(sym("h*2"))
+        );
+        assert_eq!(sym("seq").solve_factor(&sym("2")), Solved::Undecided);
+        assert_eq!(sym("7").solve_factor(&sym("2")), Solved::Impossible);
+        assert_eq!(sym("0").solve_factor(&sym("0")), Solved::Indeterminate);
+
+        assert_eq!(sym("b").decide_eq(&sym("b")), Some(true));
+        assert_eq!(sym("3").decide_eq(&sym("4")), Some(false));
+        assert_eq!(sym("b").decide_eq(&sym("4")), None);
+
+        let values = [("seq".to_string(), 5)].into();
+        assert_eq!(sym("seq^2*2").eval(&values), Some(50));
+        assert_eq!(sym("b").eval(&values), None);
+    }
+}
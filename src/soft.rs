@@ -0,0 +1,157 @@
+//! Soft assertions, which collect every failure before panicking.
+use crate::violation::{ReportHandler, with_violation_handler};
+use crate::{TensorWrapper, assert_tensor};
+use burn::prelude::{Backend, Tensor};
+use burn::tensor::BasicOps;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+/// The scope of a `soft_assert()`.
+///
+/// Checks made through the scope, or any other check on the same thread,
+/// report their failures to the scope, and continue.
+#[derive(Debug)]
+pub struct SoftAssertions {
+    report: Arc<ReportHandler>,
+}
+
+impl SoftAssertions {
+    /// Wrap a Tensor for soft assertions.
+    pub fn tensor<'a, B, const D: usize, K>(
+        &self,
+        tensor: &'a Tensor<B, D, K>,
+    ) -> TensorWrapper<'a, B, D, K>
+    where
+        B: Backend,
+        K: BasicOps<B>,
+    {
+        assert_tensor(tensor)
+    }
+
+    /// The number of failures so far.
+    #[must_use]
+    pub fn failures(&self) -> usize {
+        self.report.len()
+    }
+}
+
+/// Run every check in `f`, then panic once with a report of all failures.
+///
+/// Failures are collected from checks on the calling thread;
+/// a check on another thread is reported to that thread's handler.
+///
+/// ## Panics
+///
+/// Panics if any check failed; with a report listing every failure.
+/// If `f` itself panics, the panic is resumed; with the report appended to its message,
+/// if it has a string message.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn::backend::NdArray;
+/// use burn::tensor::Tensor;
+/// use burn_contracts::soft_assert;
+///
+/// let device = Default::default();
+/// let a = Tensor::<NdArray, 2>::zeros([2, 3], &device);
+/// let b = Tensor::<NdArray, 2>::zeros([4, 3], &device);
+///
+/// // Panics with "2 contract violation(s): ...", listing both failures.
+/// let result = std::panic::catch_unwind(|| {
+///     soft_assert(|s| {
+///         s.tensor(&a).has_dims([3, 2]);
+///         s.tensor(&b).has_named_dims([("rows", 2), ("cols", 3)]);
+///     })
+/// });
+/// assert!(result.is_err());
+/// ```
+#[track_caller]
+pub fn soft_assert<R>(f: impl FnOnce(&SoftAssertions) -> R) -> R {
+    let scope = SoftAssertions {
+        report: Arc::new(ReportHandler::default()),
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        with_violation_handler(scope.report.clone(), || f(&scope))
+    }));
+    let report = scope.report.report();
+    match (result, report) {
+        (Ok(result), None) => result,
+        (Ok(_), Some(report)) => panic!("{report}"),
+        (Err(payload), None) => panic::resume_unwind(payload),
+        (Err(payload), Some(report)) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied());
+            match message {
+                Some(message) => panic::resume_unwind(Box::new(format!("{message}\n{report}"))),
+                None => panic::resume_unwind(payload),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burn::backend::NdArray;
+
+    #[test]
+    fn test_soft_assert_passing() {
+        let device = Default::default();
+        let a = Tensor::<NdArray, 2>::zeros([2, 3], &device);
+
+        let dims = soft_assert(|s| {
+            s.tensor(&a).has_dims([2, 3]).is_close(&a, None, None);
+            assert_eq!(s.failures(), 0);
+            a.dims()
+        });
+        assert_eq!(dims, [2, 3]);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "contracts-full-values"), ignore = "contracts are off")]
+    fn test_soft_assert_failing() {
+        let device = Default::default();
+        let a = Tensor::<NdArray, 2>::zeros([2, 3], &device);
+        let b = Tensor::<NdArray, 2>::ones([2, 3], &device);
+
+        let panic = std::panic::catch_unwind(|| {
+            soft_assert(|s| {
+                s.tensor(&a).has_dims([3, 2]);
+                s.tensor(&a).equals(&b);
+                s.tensor(&b).is_close(&a, None, None);
+                assert_eq!(s.failures(), 3);
+            });
+        })
+        .unwrap_err();
+
+        let report = panic.downcast_ref::<String>().unwrap();
+        assert!(report.starts_with("3 contract violation(s):"));
+        for check in ["has_dims", "equals", "is_close"] {
+            assert!(report.contains(&format!(": {check}: ")), "{report}");
+        }
+    }
+
+    #[test]
+    fn test_soft_assert_panicking() {
+        let device = Default::default();
+        let a = Tensor::<NdArray, 2>::zeros([2, 3], &device);
+
+        let panic = std::panic::catch_unwind(|| {
+            soft_assert(|s| {
+                s.tensor(&a).has_dims([3, 2]);
+                panic!("boom");
+            });
+        })
+        .unwrap_err();
+
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert!(
+            message.starts_with("boom\n1 contract violation(s):"),
+            "{message}"
+        );
+        assert!(message.contains(": has_dims: "), "{message}");
+    }
+}
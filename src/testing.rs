@@ -1,10 +1,12 @@
-/// Testing utilities for Burn contracts.
-///
-/// These utilities are useful for writing unittests; but generally bad-practice
-/// for use in production code.
-use crate::TensorWrapper;
+//! Testing utilities for Burn contracts.
+//!
+//! These utilities are useful for writing unittests; but generally bad-practice
+//! for use in production code.
+use crate::level::ContractLevel;
+use crate::violation::{Violation, report_violation};
+use crate::{ContractError, TensorWrapper};
 use burn::prelude::{Backend, Tensor};
-use burn::tensor::{BasicOps, Numeric};
+use burn::tensor::{BasicOps, ElementConversion, Numeric};
 
 impl<B, const D: usize, K> TensorWrapper<'_, B, D, K>
 where
@@ -13,28 +15,60 @@ where
 {
     /// Assert that the wrapped tensor has the expected value.
     ///
+    /// A `ContractLevel::FullValues` check; it reads back both tensors.
+    ///
     /// ## Parameters
     ///
     /// - `expected`: The expected tensor.
     ///
     /// ## Panics
     ///
-    /// Panics if the tensor does not have the expected value.
+    /// Reports a `Violation` if the tensor does not have the expected value;
+    /// the default `PanicHandler` panics.
+    #[track_caller]
     pub fn equals(
         &self,
         expected: &Tensor<B, D, K>,
     ) -> &Self {
-        let _ = self.has_dims(expected.dims());
+        if let Err(err) = self.try_equals(expected) {
+            report_violation(&Violation::from_error(&err));
+        }
+        self
+    }
+
+    /// Check that the wrapped tensor has the expected value.
+    ///
+    /// The fallible form of `equals()`; a `ContractLevel::FullValues` check.
+    /// When that level is not enabled, at runtime or by cargo features,
+    /// the check is skipped, and `Ok` means "not checked".
+    ///
+    /// ## Parameters
+    ///
+    /// - `expected`: The expected tensor.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `ContractError::Dims` if the shapes differ,
+    /// or `ContractError::Data` if the data differs.
+    pub fn try_equals(
+        &self,
+        expected: &Tensor<B, D, K>,
+    ) -> Result<&Self, ContractError> {
+        self.try_has_dims(expected.dims())?;
+        if !ContractLevel::FullValues.is_enabled() {
+            return Ok(self);
+        }
 
-        assert_eq!(
-            self.inner.to_data(),
-            expected.to_data(),
-            "Expected tensor to have data {:?}, but got {:?}",
-            expected.to_data(),
-            self.inner.to_data()
-        );
+        let actual = self.inner.to_data();
+        let expected = expected.to_data();
+        if actual != expected {
+            return Err(ContractError::Data {
+                expected: format!("{expected:?}"),
+                actual: format!("{actual:?}"),
+            });
+        }
 
-        self
+        Ok(self)
     }
 }
 
@@ -48,6 +82,9 @@ where
 {
     /// Assert that the wrapped tensor is close to the expected tensor.
     ///
+    /// A `ContractLevel::CheapValues` check; the comparison is reduced on the device,
+    /// and only the count of close elements is read back.
+    ///
     /// ## Parameters
     ///
     /// - `expected`: The expected tensor.
@@ -56,14 +93,47 @@ where
     ///
     /// ## Panics
     ///
-    /// Panics if the tensor is not close to the expected tensor.
+    /// Reports a `Violation` if the tensor is not close to the expected tensor;
+    /// the default `PanicHandler` panics.
+    #[track_caller]
     pub fn is_close(
         &self,
         expected: &Tensor<B, D, K>,
         atol: Option<f64>,
         rtol: Option<f64>,
     ) -> &Self {
-        self.has_dims(expected.dims());
+        if let Err(err) = self.try_is_close(expected, atol, rtol) {
+            report_violation(&Violation::from_error(&err));
+        }
+        self
+    }
+
+    /// Check that the wrapped tensor is close to the expected tensor.
+    ///
+    /// The fallible form of `is_close()`; a `ContractLevel::CheapValues` check.
+    /// When that level is not enabled, at runtime or by cargo features,
+    /// the check is skipped, and `Ok` means "not checked".
+    ///
+    /// ## Parameters
+    ///
+    /// - `expected`: The expected tensor.
+    /// - `atol`: The absolute tolerance, which defaults to `DEFAULT_ATOL`.
+    /// - `rtol`: The relative tolerance, which defaults to `DEFAULT_RTOL`.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `ContractError::Dims` if the shapes differ,
+    /// or `ContractError::NotClose` if any element is not close.
+    pub fn try_is_close(
+        &self,
+        expected: &Tensor<B, D, K>,
+        atol: Option<f64>,
+        rtol: Option<f64>,
+    ) -> Result<&Self, ContractError> {
+        self.try_has_dims(expected.dims())?;
+        if !ContractLevel::CheapValues.is_enabled() {
+            return Ok(self);
+        }
 
         // reference implementation:
         // - Tensor::is_close()
@@ -77,29 +147,36 @@ where
             .clone()
             .is_close(expected.clone(), Some(atol), Some(rtol));
 
-        let data = close.clone().into_data();
-        let num_elements = data.num_elements();
-
-        // Count the number of elements that are close (true)
-        let count = data.iter::<bool>().filter(|x| *x).count();
-
-        if count != num_elements {
-            #[allow(clippy::cast_precision_loss)]
-            let percentage = (count as f64 / num_elements as f64) * 100.0;
-
-            panic!(
-                "Expected tensor to be within (atol={atol:?}, rtol={rtol:?}) of target\n\
-                 - {count}/{num_elements} ({percentage:.2}%) elements passed",
-            );
+        let num_elements = close.shape().num_elements();
+        if close.clone().all().into_scalar().elem::<bool>() {
+            return Ok(self);
         }
 
-        self
+        // Count the close elements for the report. The int sum cannot overflow a 32-bit
+        // int element below i32::MAX elements; larger tensors read back the mask instead.
+        let count = if i32::try_from(num_elements).is_ok() {
+            usize::try_from(close.int().sum().into_scalar().elem::<i64>())
+                .expect("the count is at most i32::MAX")
+        } else {
+            close
+                .into_data()
+                .iter::<bool>()
+                .filter(|is_close| *is_close)
+                .count()
+        };
+
+        Err(ContractError::NotClose {
+            atol,
+            rtol,
+            passed: count,
+            total: num_elements,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::assert_tensor;
+    use crate::{ContractError, assert_tensor};
     use burn::backend::NdArray;
     use burn::prelude::Backend;
     use burn::tensor::Tensor;
@@ -120,6 +197,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(not(feature = "contracts-cheap-values"), ignore = "contracts are off")]
     #[should_panic(
         expected = "Expected tensor to be within (atol=1e-8, rtol=1e-5) of target\n\
                     - 3/4 (75.00%) elements passed"
@@ -138,4 +216,31 @@ mod tests {
 
         assert_tensor(&tensor).is_close(&other, None, None);
     }
+
+    #[test]
+    #[cfg_attr(not(feature = "contracts-full-values"), ignore = "contracts are off")]
+    fn test_try_values() {
+        let device = Default::default();
+        let tensor = Tensor::<NdArray, 2>::from_data([[2., 3.], [4., 5.]], &device);
+        let other = Tensor::<NdArray, 2>::from_data([[2., 3.], [4., 6.]], &device);
+        let wide = Tensor::<NdArray, 2>::zeros([2, 3], &device);
+
+        assert!(assert_tensor(&tensor).try_equals(&tensor).is_ok());
+        assert!(matches!(
+            assert_tensor(&tensor).try_equals(&other),
+            Err(ContractError::Data { .. })
+        ));
+        assert!(matches!(
+            assert_tensor(&tensor).try_is_close(&other, None, None),
+            Err(ContractError::NotClose {
+                passed: 3,
+                total: 4,
+                ..
+            })
+        ));
+        assert!(matches!(
+            assert_tensor(&tensor).try_is_close(&wide, None, None),
+            Err(ContractError::Dims { .. })
+        ));
+    }
 }
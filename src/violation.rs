@@ -0,0 +1,330 @@
+//! Contract violation handlers.
+//!
+//! A failed `TensorWrapper` assertion, or `#[shape_contract]`, is reported as a `Violation`
+//! to the current `ViolationHandler`; which panics by default. Installing another handler,
+//! process-wide with `set_violation_handler()` or on a thread with `with_violation_handler()`,
+//! lets a service log or count violations and continue.
+use crate::error::ContractError;
+use crate::level::ContractLevel;
+use once_cell::sync::Lazy;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+/// A failed contract check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The name of the check, such as `has_dims`.
+    pub check: &'static str,
+
+    /// The level of the check.
+    pub level: ContractLevel,
+
+    /// The failure message.
+    pub message: String,
+
+    /// The caller of the check.
+    pub location: &'static Location<'static>,
+}
+
+impl Violation {
+    /// A violation of `check`, at the caller's location.
+    #[must_use]
+    #[track_caller]
+    pub fn new(
+        check: &'static str,
+        level: ContractLevel,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            check,
+            level,
+            message: message.into(),
+            location: Location::caller(),
+        }
+    }
+
+    /// A violation of the check which failed with `err`, at the caller's location;
+    /// the message is `err.render()`.
+    #[must_use]
+    #[track_caller]
+    pub fn from_error(err: &ContractError) -> Self {
+        Self::new(err.check(), err.level(), err.render())
+    }
+}
+
+impl Display for Violation {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{}: {}: {}", self.location, self.check, self.message)
+    }
+}
+
+/// Handles contract violations.
+pub trait ViolationHandler: Send + Sync {
+    /// Handle a violation; returning continues past the failed check.
+    fn handle(
+        &self,
+        violation: &Violation,
+    );
+}
+
+/// Panic on every violation; the default handler.
+///
+/// The panic message ends with the location of the failed check;
+/// the panic itself is raised in the handler.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PanicHandler;
+
+impl ViolationHandler for PanicHandler {
+    fn handle(
+        &self,
+        violation: &Violation,
+    ) {
+        panic!("{} (at {})", violation.message, violation.location);
+    }
+}
+
+/// Log every violation as a `log` warning, with target `burn_contracts`; and continue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LogHandler;
+
+impl ViolationHandler for LogHandler {
+    fn handle(
+        &self,
+        violation: &Violation,
+    ) {
+        log::warn!(target: "burn_contracts", "contract violation: {violation}");
+    }
+}
+
+/// Count violations, and continue.
+#[derive(Debug, Default)]
+pub struct CountingHandler {
+    count: AtomicU64,
+}
+
+impl CountingHandler {
+    /// The number of violations so far.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Reset the count to zero, returning the previous count.
+    pub fn reset(&self) -> u64 {
+        self.count.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl ViolationHandler for CountingHandler {
+    fn handle(
+        &self,
+        _violation: &Violation,
+    ) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Collect violations into a report, and continue.
+#[derive(Debug, Default)]
+pub struct ReportHandler {
+    violations: Mutex<Vec<Violation>>,
+}
+
+impl ReportHandler {
+    /// The violations so far.
+    #[must_use]
+    pub fn violations(&self) -> Vec<Violation> {
+        self.lock().clone()
+    }
+
+    /// The number of violations so far.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether there are no violations so far.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Take the violations so far, leaving the report empty.
+    pub fn take(&self) -> Vec<Violation> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Render the violations so far, one per line; `None` if there are none.
+    #[must_use]
+    pub fn report(&self) -> Option<String> {
+        let violations = self.lock();
+        if violations.is_empty() {
+            return None;
+        }
+        let mut report = format!("{} contract violation(s):", violations.len());
+        for violation in violations.iter() {
+            report.push_str(&format!("\n- {violation}"));
+        }
+        Some(report)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Violation>> {
+        // Violations are only ever pushed; so a poisoned lock is still usable.
+        self.violations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl ViolationHandler for ReportHandler {
+    fn handle(
+        &self,
+        violation: &Violation,
+    ) {
+        self.lock().push(violation.clone());
+    }
+}
+
+/// Handle violations with a closure; e.g. to emit a metric and a log line.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn_contracts::violation::{FnHandler, set_violation_handler};
+/// use std::sync::Arc;
+///
+/// set_violation_handler(Arc::new(FnHandler(|violation| {
+///     eprintln!("contract violation: {violation}");
+/// })));
+/// # set_violation_handler(Arc::new(burn_contracts::violation::PanicHandler));
+/// ```
+pub struct FnHandler<F>(pub F)
+where
+    F: Fn(&Violation) + Send + Sync;
+
+impl<F> ViolationHandler for FnHandler<F>
+where
+    F: Fn(&Violation) + Send + Sync,
+{
+    fn handle(
+        &self,
+        violation: &Violation,
+    ) {
+        (self.0)(violation);
+    }
+}
+
+static GLOBAL: Lazy<RwLock<Arc<dyn ViolationHandler>>> =
+    Lazy::new(|| RwLock::new(Arc::new(PanicHandler)));
+
+thread_local! {
+    static SCOPED: RefCell<Option<Arc<dyn ViolationHandler>>> = const { RefCell::new(None) };
+}
+
+/// Set the process-wide violation handler.
+pub fn set_violation_handler(handler: Arc<dyn ViolationHandler>) {
+    *GLOBAL.write().unwrap_or_else(PoisonError::into_inner) = handler;
+}
+
+/// Get the current violation handler.
+///
+/// This is the handler of the innermost `with_violation_handler()` on this thread;
+/// or the process-wide handler.
+#[must_use]
+pub fn violation_handler() -> Arc<dyn ViolationHandler> {
+    SCOPED.with_borrow(Clone::clone).unwrap_or_else(|| {
+        GLOBAL
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    })
+}
+
+/// Run `f` with the violation handler overridden on this thread.
+///
+/// ## Example
+///
+/// ```rust
+/// use burn::backend::NdArray;
+/// use burn::tensor::Tensor;
+/// use burn_contracts::assert_tensor;
+/// use burn_contracts::violation::{CountingHandler, with_violation_handler};
+/// use std::sync::Arc;
+///
+/// let device = Default::default();
+/// let tensor = Tensor::<NdArray, 2>::zeros([2, 3], &device);
+///
+/// let counter = Arc::new(CountingHandler::default());
+/// with_violation_handler(counter.clone(), || {
+///     assert_tensor(&tensor).has_dims([3, 2]).has_dims([2, 3]);
+/// });
+/// assert_eq!(counter.count(), 1);
+/// ```
+pub fn with_violation_handler<R>(
+    handler: Arc<dyn ViolationHandler>,
+    f: impl FnOnce() -> R,
+) -> R {
+    struct Restore(Option<Arc<dyn ViolationHandler>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            SCOPED.with_borrow_mut(|scoped| *scoped = previous);
+        }
+    }
+
+    let _restore = Restore(SCOPED.with_borrow_mut(|scoped| scoped.replace(handler)));
+    f()
+}
+
+/// Report a violation to the current handler.
+///
+/// ## Panics
+///
+/// Panics if the handler does; the default `PanicHandler` always does.
+pub fn report_violation(violation: &Violation) {
+    violation_handler().handle(violation);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_handler() {
+        let report = Arc::new(ReportHandler::default());
+        let counter = Arc::new(CountingHandler::default());
+
+        with_violation_handler(report.clone(), || {
+            report_violation(&Violation::new("a", ContractLevel::Shapes, "first"));
+            with_violation_handler(counter.clone(), || {
+                report_violation(&Violation::new("b", ContractLevel::Shapes, "hidden"));
+            });
+            report_violation(&Violation::new("c", ContractLevel::FullValues, "second"));
+        });
+        assert_eq!(counter.count(), 1);
+
+        assert_eq!(report.len(), 2);
+        let text = report.report().unwrap();
+        assert!(text.starts_with("2 contract violation(s):\n- "));
+        assert!(text.contains("violation.rs"));
+        assert!(text.ends_with(": c: second"));
+
+        let violations = report.take();
+        assert_eq!(violations[0].check, "a");
+        assert_eq!(violations[1].level, ContractLevel::FullValues);
+        assert_eq!(report.report(), None);
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "boom (at ")]
+    fn test_panic_handler() {
+        PanicHandler.handle(&Violation::new("a", ContractLevel::Shapes, "boom"));
+    }
+}